@@ -1,31 +1,369 @@
+/// Span of an AST node, i.e. the byte offsets of its start and end
+/// in the source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Creates a new span.
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// AST nodes with spans.
+pub trait Spanned {
+    fn span(&self) -> Span;
+}
+
+/// Error reported by the actions in the grammar, and its span.
+pub type UserError = (Span, &'static str);
+
 #[derive(Debug)]
 pub struct CompUnit {
-    pub func_def: FuncDef,
+    pub items: Vec<GlobalItem>,
+}
+
+#[derive(Debug)]
+pub enum GlobalItem {
+    Decl(Decl),
+    FuncDef(FuncDef),
+    /// Item failed to parse, the error has been reported by the parser.
+    Error,
 }
 
 #[derive(Debug)]
 pub struct FuncDef {
     pub func_type: FuncType,
     pub ident: String,
+    pub params: Vec<FuncFParam>,
     pub block: Block,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub enum FuncType {
     Int,
+    Float,
+    Void,
+}
+
+#[derive(Debug)]
+pub struct FuncFParam {
+    pub ty: BType,
+    pub ident: String,
+    pub dims: Option<Vec<ConstExp>>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Decl {
+    Const(ConstDecl),
+    Var(VarDecl),
+}
+
+#[derive(Debug)]
+pub struct ConstDecl {
+    pub ty: BType,
+    pub defs: Vec<ConstDef>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BType {
+    Int,
+    Float,
+}
+
+#[derive(Debug)]
+pub struct ConstDef {
+    pub ident: String,
+    pub dims: Vec<ConstExp>,
+    pub init: ConstInitVal,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ConstInitVal {
+    Exp(ConstExp),
+    List(Vec<ConstInitVal>),
+}
+
+#[derive(Debug)]
+pub struct VarDecl {
+    pub ty: BType,
+    pub defs: Vec<VarDef>,
+}
+
+#[derive(Debug)]
+pub struct VarDef {
+    pub ident: String,
+    pub dims: Vec<ConstExp>,
+    pub init: Option<InitVal>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum InitVal {
+    Exp(Exp),
+    List(Vec<InitVal>),
 }
 
 #[derive(Debug)]
 pub struct Block {
-    pub stmt: Stmt,
+    pub items: Vec<BlockItem>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum BlockItem {
+    Decl(Decl),
+    Stmt(Stmt),
 }
 
 #[derive(Debug)]
 pub enum Stmt {
+    Assign(Box<Assign>),
+    ExpStmt(ExpStmt),
+    Block(Block),
+    If(Box<If>),
+    While(Box<While>),
+    Break(Break),
+    Continue(Continue),
     Return(Return),
 }
 
+#[derive(Debug)]
+pub struct Assign {
+    pub lval: LVal,
+    pub exp: Exp,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct ExpStmt {
+    pub exp: Option<Exp>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct If {
+    pub cond: Exp,
+    pub then: Stmt,
+    pub else_then: Option<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct While {
+    pub cond: Exp,
+    pub body: Stmt,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Break {
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Continue {
+    pub span: Span,
+}
+
 #[derive(Debug)]
 pub struct Return {
-    pub num: i32,
+    pub exp: Option<Exp>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Exp {
+    pub lor: Box<LOrExp>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct ConstExp {
+    pub exp: Exp,
+}
+
+#[derive(Debug)]
+pub struct LVal {
+    pub ident: String,
+    pub indices: Vec<Exp>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum PrimaryExp {
+    Exp(Box<Exp>),
+    LVal(LVal),
+    Number(i32, Span),
+    Float(f32, Span),
+}
+
+#[derive(Debug)]
+pub enum UnaryExp {
+    Primary(PrimaryExp),
+    Call(FuncCall),
+    Unary(UnaryOp, Box<UnaryExp>, Span),
+}
+
+#[derive(Debug)]
+pub struct FuncCall {
+    pub ident: String,
+    pub args: Vec<Exp>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum MulExp {
+    Unary(UnaryExp),
+    MulUnary(Box<MulExp>, MulOp, UnaryExp, Span),
+}
+
+#[derive(Debug)]
+pub enum AddExp {
+    Mul(MulExp),
+    AddMul(Box<AddExp>, AddOp, MulExp, Span),
+}
+
+#[derive(Debug)]
+pub enum RelExp {
+    Add(AddExp),
+    RelAdd(Box<RelExp>, RelOp, AddExp, Span),
+}
+
+#[derive(Debug)]
+pub enum EqExp {
+    Rel(RelExp),
+    EqRel(Box<EqExp>, EqOp, RelExp, Span),
+}
+
+#[derive(Debug)]
+pub enum LAndExp {
+    Eq(EqExp),
+    LAndEq(Box<LAndExp>, EqExp, Span),
+}
+
+#[derive(Debug)]
+pub enum LOrExp {
+    LAnd(LAndExp),
+    LOrLAnd(Box<LOrExp>, LAndExp, Span),
+}
+
+impl Spanned for Exp {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Spanned for PrimaryExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Exp(exp) => exp.span,
+            Self::LVal(lval) => lval.span,
+            Self::Number(_, span) | Self::Float(_, span) => *span,
+        }
+    }
+}
+
+impl Spanned for UnaryExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Primary(exp) => exp.span(),
+            Self::Call(call) => call.span,
+            Self::Unary(_, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for MulExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Unary(exp) => exp.span(),
+            Self::MulUnary(_, _, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for AddExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Mul(exp) => exp.span(),
+            Self::AddMul(_, _, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for RelExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Add(exp) => exp.span(),
+            Self::RelAdd(_, _, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for EqExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Rel(exp) => exp.span(),
+            Self::EqRel(_, _, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for LAndExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::Eq(exp) => exp.span(),
+            Self::LAndEq(_, _, span) => *span,
+        }
+    }
+}
+
+impl Spanned for LOrExp {
+    fn span(&self) -> Span {
+        match self {
+            Self::LAnd(exp) => exp.span(),
+            Self::LOrLAnd(_, _, span) => *span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UnaryOp {
+    Pos,
+    Neg,
+    Not,
+}
+
+#[derive(Debug)]
+pub enum MulOp {
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug)]
+pub enum AddOp {
+    Add,
+    Sub,
+}
+
+#[derive(Debug)]
+pub enum RelOp {
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug)]
+pub enum EqOp {
+    Eq,
+    Neq,
 }
@@ -0,0 +1,122 @@
+use koopa::ir::values::Call;
+use koopa::ir::{FunctionData, Program, TypeKind, ValueKind};
+
+use crate::code::values::{ARG_REGS, FARG_REGS};
+use crate::ir::FloatSigs;
+
+/// Calling convention (ilp32f) of functions in a program.
+///
+/// `float`s are passed in `fa0`-`fa7`, then in integer argument registers
+/// and on the stack like integers, and are returned in `fa0`.
+#[derive(Clone, Copy)]
+pub struct Abi<'p> {
+    program: &'p Program,
+    float_sigs: &'p FloatSigs,
+}
+
+/// Location of an argument.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArgLoc {
+    Reg(&'static str),
+    FReg(&'static str),
+    /// Index of the word in the outgoing argument area of the caller.
+    Stack(usize),
+}
+
+/// Soft-float routine of libgcc, calls to which are replaced with
+/// F extension instructions.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FloatOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    FromInt,
+    ToInt,
+}
+
+impl<'p> Abi<'p> {
+    /// Creates the calling convention of the given program.
+    pub fn new(program: &'p Program, float_sigs: &'p FloatSigs) -> Self {
+        Self {
+            program,
+            float_sigs,
+        }
+    }
+
+    /// Returns locations of parameters of the given function.
+    pub fn params(&self, func: &FunctionData) -> Vec<ArgLoc> {
+        let params_len = match func.ty().kind() {
+            TypeKind::Function(params, _) => params.len(),
+            _ => unreachable!(),
+        };
+        let floats = self.float_sigs.get(func.name()).map(|sig| &sig.params);
+        let (mut regs, mut fregs, mut stack) = (0, 0, 0);
+        (0..params_len)
+            .map(|i| {
+                let is_float = floats.is_some_and(|floats| floats[i]);
+                if is_float && fregs < FARG_REGS.len() {
+                    fregs += 1;
+                    ArgLoc::FReg(FARG_REGS[fregs - 1])
+                } else if regs < ARG_REGS.len() {
+                    regs += 1;
+                    ArgLoc::Reg(ARG_REGS[regs - 1])
+                } else {
+                    stack += 1;
+                    ArgLoc::Stack(stack - 1)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns locations of arguments of the given call.
+    pub fn args(&self, call: &Call) -> Vec<ArgLoc> {
+        self.params(self.program.func(call.callee()))
+    }
+
+    /// Returns the register holding the return value of the given function.
+    pub fn ret_reg(&self, func: &FunctionData) -> &'static str {
+        match self.float_sigs.get(func.name()) {
+            Some(sig) if sig.ret => "fa0",
+            _ => "a0",
+        }
+    }
+
+    /// Returns the register holding the result of the given call.
+    pub fn result_reg(&self, call: &Call) -> &'static str {
+        self.ret_reg(self.program.func(call.callee()))
+    }
+
+    /// Returns the soft-float routine called by the given call, if any.
+    pub fn float_op(&self, call: &Call) -> Option<FloatOp> {
+        Some(match self.program.func(call.callee()).name() {
+            "@__addsf3" => FloatOp::Add,
+            "@__subsf3" => FloatOp::Sub,
+            "@__mulsf3" => FloatOp::Mul,
+            "@__divsf3" => FloatOp::Div,
+            "@__negsf2" => FloatOp::Neg,
+            "@__eqsf2" => FloatOp::Eq,
+            "@__nesf2" => FloatOp::Ne,
+            "@__ltsf2" => FloatOp::Lt,
+            "@__lesf2" => FloatOp::Le,
+            "@__gtsf2" => FloatOp::Gt,
+            "@__gesf2" => FloatOp::Ge,
+            "@__floatsisf" => FloatOp::FromInt,
+            "@__fixsfsi" => FloatOp::ToInt,
+            _ => return None,
+        })
+    }
+
+    /// Returns `true` if the given instruction is a call that is not
+    /// replaced with F extension instructions.
+    pub fn is_call(&self, kind: &ValueKind) -> bool {
+        matches!(kind, ValueKind::Call(call) if self.float_op(call).is_none())
+    }
+}
@@ -1,17 +1,16 @@
-use std::fs::File;
 use std::io::{Result, Write};
 
 use crate::code::func::FunctionInfo;
 
 /// Assembly builder.
 pub struct AsmBuilder<'f> {
-    f: &'f mut File,
+    f: &'f mut Vec<u8>,
     temp: &'static str,
 }
 
 impl<'f> AsmBuilder<'f> {
     /// Creates a new assembly builder.
-    pub fn new(f: &'f mut File, temp: &'static str) -> Self {
+    pub fn new(f: &'f mut Vec<u8>, temp: &'static str) -> Self {
         Self { f, temp }
     }
 
@@ -32,6 +31,38 @@ impl<'f> AsmBuilder<'f> {
         }
     }
 
+    pub fn op2(&mut self, op: &str, dest: &str, lhs: &str, rhs: &str) -> Result<()> {
+        writeln!(self.f, "  {op} {dest}, {lhs}, {rhs}")
+    }
+
+    pub fn op1(&mut self, op: &str, dest: &str, src: &str) -> Result<()> {
+        writeln!(self.f, "  {op} {dest}, {src}")
+    }
+
+    pub fn seqz(&mut self, dest: &str, src: &str) -> Result<()> {
+        writeln!(self.f, "  seqz {dest}, {src}")
+    }
+
+    pub fn snez(&mut self, dest: &str, src: &str) -> Result<()> {
+        writeln!(self.f, "  snez {dest}, {src}")
+    }
+
+    pub fn muli(&mut self, dest: &str, opr: &str, imm: i32) -> Result<()> {
+        if imm == 0 {
+            self.mv(dest, "x0")
+        } else if imm > 0 && (imm & (imm - 1)) == 0 {
+            let shift = imm.trailing_zeros();
+            if shift == 0 {
+                self.mv(dest, opr)
+            } else {
+                writeln!(self.f, "  slli {dest}, {opr}, {shift}")
+            }
+        } else {
+            self.li(self.temp, imm)?;
+            writeln!(self.f, "  mul {dest}, {opr}, {}", self.temp)
+        }
+    }
+
     pub fn mv(&mut self, dest: &str, src: &str) -> Result<()> {
         if dest != src {
             writeln!(self.f, "  mv {dest}, {src}")
@@ -58,10 +89,44 @@ impl<'f> AsmBuilder<'f> {
         }
     }
 
+    pub fn fsw(&mut self, src: &str, addr: &str, offset: i32) -> Result<()> {
+        if (-2048..=2047).contains(&offset) {
+            writeln!(self.f, "  fsw {src}, {offset}({addr})")
+        } else {
+            self.addi(self.temp, addr, offset)?;
+            writeln!(self.f, "  fsw {src}, 0({})", self.temp)
+        }
+    }
+
+    pub fn flw(&mut self, dest: &str, addr: &str, offset: i32) -> Result<()> {
+        if (-2048..=2047).contains(&offset) {
+            writeln!(self.f, "  flw {dest}, {offset}({addr})")
+        } else {
+            self.addi(self.temp, addr, offset)?;
+            writeln!(self.f, "  flw {dest}, 0({})", self.temp)
+        }
+    }
+
+    pub fn bnez(&mut self, cond: &str, label: &str) -> Result<()> {
+        writeln!(self.f, "  bnez {cond}, {label}")
+    }
+
+    pub fn beqz(&mut self, cond: &str, label: &str) -> Result<()> {
+        writeln!(self.f, "  beqz {cond}, {label}")
+    }
+
+    pub fn label(&mut self, label: &str) -> Result<()> {
+        writeln!(self.f, "{label}:")
+    }
+
     pub fn j(&mut self, label: &str) -> Result<()> {
         writeln!(self.f, "  j {label}")
     }
 
+    pub fn call(&mut self, func: &str) -> Result<()> {
+        writeln!(self.f, "  call {func}")
+    }
+
     pub fn prologue(&mut self, func_name: &str, info: &FunctionInfo) -> Result<()> {
         // declaration
         writeln!(self.f, "  .text")?;
@@ -75,6 +140,9 @@ impl<'f> AsmBuilder<'f> {
             if !info.is_leaf() {
                 self.sw("ra", "sp", offset - 4)?;
             }
+            for (reg, offset) in info.saved_regs() {
+                self.sw(reg, "sp", offset as i32)?;
+            }
         }
         Ok(())
     }
@@ -85,6 +153,9 @@ impl<'f> AsmBuilder<'f> {
             if !info.is_leaf() {
                 self.lw("ra", "sp", offset - 4)?;
             }
+            for (reg, offset) in info.saved_regs() {
+                self.lw(reg, "sp", offset as i32)?;
+            }
             self.addi("sp", "sp", offset)?;
         }
         writeln!(self.f, "  ret")
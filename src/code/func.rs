@@ -4,30 +4,41 @@ use std::collections::HashMap;
 use koopa::ir::entities::ValueData;
 use koopa::ir::{BasicBlock, Function, TypeKind, ValueKind};
 
+use crate::code::values::CALLEE_SAVED_REGS;
+
 /// Function information.
 pub struct FunctionInfo {
     func: Function,
-    /// Maximum argument number of call instructions in the function.
-    /// `None` if the current function is a leaf function.
-    max_arg_num: Option<usize>,
+    /// Maximum number of arguments passed on the stack by call instructions
+    /// in the function. `None` if the current function is a leaf function.
+    max_stack_args: Option<usize>,
     alloc_size: usize,
+    /// `true` if branch targets may be out of the range of
+    /// conditional branch instructions (±4 KiB).
+    long_branch: bool,
     allocs: HashMap<*const ValueData, Slot>,
+    regs: HashMap<*const ValueData, &'static str>,
+    /// Callee-saved registers used by the function.
+    saved_regs: Vec<&'static str>,
     bbs: HashMap<BasicBlock, String>,
     sp_offset: Cell<Option<usize>>,
 }
 
 impl FunctionInfo {
     thread_local! {
-        static NEXT_TEMP_LABEL_ID: Cell<usize> = Cell::new(0);
+        static NEXT_TEMP_LABEL_ID: Cell<usize> = const { Cell::new(0) };
     }
 
     /// Creates a new function information.
     pub fn new(func: Function) -> Self {
       Self {
             func,
-            max_arg_num: None,
+            max_stack_args: None,
             alloc_size: 0,
+            long_branch: false,
             allocs: HashMap::new(),
+            regs: HashMap::new(),
+            saved_regs: Vec::new(),
             bbs: HashMap::new(),
             sp_offset: Cell::new(None),
         }
@@ -38,16 +49,34 @@ impl FunctionInfo {
         self.func
     }
 
-    /// Logs argument number.
-    pub fn log_arg_num(&mut self, arg_num: usize) {
-        if self.max_arg_num.is_none() || arg_num > self.max_arg_num.unwrap() {
-            self.max_arg_num = Some(arg_num);
+    /// Logs the number of arguments passed on the stack by a call.
+    pub fn log_stack_args(&mut self, num: usize) {
+        if self.max_stack_args.is_none() || num > self.max_stack_args.unwrap() {
+            self.max_stack_args = Some(num);
         }
     }
 
     /// Returns `true` if the current function is a leaf function.
     pub fn is_leaf(&self) -> bool {
-        self.max_arg_num.is_none()
+        self.max_stack_args.is_none()
+    }
+
+    /// Marks that branch targets in the current function may be
+    /// out of the range of conditional branch instructions.
+    pub fn set_long_branch(&mut self) {
+        self.long_branch = true;
+    }
+
+    /// Returns `true` if branch targets in the current function may be
+    /// out of the range of conditional branch instructions (±4 KiB).
+    pub fn has_long_branch(&self) -> bool {
+        self.long_branch
+    }
+
+    /// Returns a new unique temporary label.
+    pub fn temp_label(&self) -> String {
+        let id = Self::NEXT_TEMP_LABEL_ID.with(|id| id.replace(id.get() + 1));
+        format!(".L{}", id)
     }
 
     /// Returns a reference to the name of the given basic block.
@@ -84,36 +113,63 @@ impl FunctionInfo {
         };
     }
 
+    /// Assigns the given register to the given value data.
+    pub fn set_reg(&mut self, value: &ValueData, reg: &'static str) {
+        self.regs.insert(value, reg);
+        if CALLEE_SAVED_REGS.contains(&reg) && !self.saved_regs.contains(&reg) {
+            self.saved_regs.push(reg);
+        }
+    }
+
+    /// Returns the register of the given value data,
+    /// `None` if the value is not in a register.
+    pub fn reg(&self, value: &ValueData) -> Option<&'static str> {
+        self.regs.get(&(value as *const ValueData)).copied()
+    }
+
+    /// Returns callee-saved registers used by the function,
+    /// and their slot offsets (relative to `sp`).
+    pub fn saved_regs(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        let top = self.sp_offset() - self.ra_size();
+        self.saved_regs
+            .iter()
+            .enumerate()
+            .map(move |(i, &reg)| (reg, top - (i + 1) * 4))
+    }
+
     /// Returns the slot offset (relative to `sp`) of the given value data.
     pub fn slot_offset(&self, value: &ValueData) -> Option<Slot> {
         self.allocs.get(&(value as *const ValueData)).map(|&offset| {
-            if self.is_leaf() {
-                offset.map(|o| self.sp_offset() - self.alloc_size + o)
-            } else {
-                offset.map(|o| self.sp_offset() - 4 - self.alloc_size + o)
-            }
+            let saved = self.saved_regs.len() * 4;
+            offset.map(|o| self.sp_offset() - self.ra_size() - saved - self.alloc_size + o)
         })
     }
 
+    /// Returns the size of the slot for storing return address.
+    fn ra_size(&self) -> usize {
+        if self.is_leaf() {
+            0
+        } else {
+            4
+        }
+    }
+
     /// Returns the stack pointer offset.
     pub fn sp_offset(&self) -> usize {
         if let Some(sp_offset) = self.sp_offset.get() {
             sp_offset
         } else {
-            // slot for storing return address
-            let ra = if self.is_leaf() { 0 } else { 4 };
+            // slots for storing return address and callee-saved registers
+            let saved = self.ra_size() + self.saved_regs.len() * 4;
 
             // slot for storing arguments
-            let args = match self.max_arg_num {
-                Some(num) if num > 8 => (num - 8) * 4,
-                _ => 0,
-            };
+            let args = self.max_stack_args.unwrap_or(0) * 4;
 
             // the final offset
-            let offset = ra + self.alloc_size + args;
+            let offset = saved + self.alloc_size + args;
 
             // align to 16 bytes
-            let sp_offset = (offset + 15) / 16 * 16;
+            let sp_offset = offset.div_ceil(16) * 16;
             self.sp_offset.set(Some(sp_offset));
             sp_offset
         }
@@ -121,7 +177,7 @@ impl FunctionInfo {
 }
 
 /// A stack slot.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Slot {
     pub offset: usize,
     /// `true` if the slot stores an pointer but not an allocation.
@@ -131,7 +187,7 @@ pub struct Slot {
 
 impl Slot {
     /// Creates a new stack slot.
-    fn new(offset: usize, is_ptr: bool) -> Self {
+    pub fn new(offset: usize, is_ptr: bool) -> Self {
         Self { offset, is_ptr }
     }
   
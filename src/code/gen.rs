@@ -1,20 +1,23 @@
-use std::fs::File;
-use std::io::{Result, Write};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result, Write};
 
 use koopa::ir::entities::ValueData;
 use koopa::ir::values::*;
-use koopa::ir::{BasicBlock, FunctionData, Program, Value, ValueKind};
+use koopa::ir::{BasicBlock, FunctionData, Program, TypeKind, Value, ValueKind};
 
+use crate::code::abi::{ArgLoc, FloatOp};
 use crate::code::builder::AsmBuilder;
-use crate::code::func::FunctionInfo;
+use crate::code::func::{FunctionInfo, Slot};
 use crate::code::info::{cur_func, cur_func_mut, ProgramInfo};
+use crate::code::liveness::{is_candidate, layout_values, Liveness};
 use crate::code::values::{asm_value, AsmValue};
+use crate::code::{graph_coloring, linear_scan, RegAlloc};
 
 /// Trait for generating RISC-V assembly.
 pub trait GenerateToAsm<'p, 'i> {
     type Out;
 
-    fn generate(&self, f: &mut File, info: &'i mut ProgramInfo<'p>) -> Result<Self::Out>;
+    fn generate(&self, f: &mut Vec<u8>, info: &'i mut ProgramInfo<'p>) -> Result<Self::Out>;
 }
 
 /// Trait for generating RISC-V assembly (for values).
@@ -23,7 +26,7 @@ trait GenerateValueToAsm<'p, 'i> {
 
     fn generate(
         &self,
-        f: &mut File,
+        f: &mut Vec<u8>,
         info: &'i mut ProgramInfo<'p>,
         v: &ValueData,
     ) -> Result<Self::Out>;
@@ -32,7 +35,7 @@ trait GenerateValueToAsm<'p, 'i> {
 impl<'p, 'i> GenerateToAsm<'p, 'i> for Program {
     type Out = ();
 
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
         // generate global allocations
         for &value in self.inst_layout() {
             let data = self.borrow_value(value);
@@ -57,52 +60,71 @@ impl<'p, 'i> GenerateToAsm<'p, 'i> for Program {
 impl<'p, 'i> GenerateToAsm<'p, 'i> for FunctionData {
     type Out = ();
 
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
         // skip declarations
         if self.layout().entry_bb().is_none() {
             return Ok(());
         }
 
-        // allocation stack slots and log argument number
+        // allocate registers
+        let abi = info.abi();
+        let regs = match info.reg_alloc() {
+            RegAlloc::None => HashMap::new(),
+            RegAlloc::LinearScan => {
+                linear_scan::allocate(self, &Liveness::analyze(self, abi), abi)
+            }
+            RegAlloc::GraphColoring => {
+                graph_coloring::allocate(self, &Liveness::analyze(self, abi), abi)
+            }
+        };
+
+        // assign registers/stack slots, log number of stack arguments
         let func = cur_func_mut!(info);
-        for value in self.dfg().values().values() {
-            // allocate stack slot
-            if value.kind().is_local_inst() && !value.used_by().is_empty() {
-                func.alloc_slot(value);
+        for value in layout_values(self) {
+            // assign register or allocate stack slot for spilled value
+            let data = self.dfg().value(value);
+            if let Some(&reg) = regs.get(&value) {
+                func.set_reg(data, reg);
+            } else if is_candidate(self, value)
+                || matches!(data.kind(), ValueKind::Alloc(_)) && !data.used_by().is_empty()
+            {
+                func.alloc_slot(data);
             }
 
-            // log argument number
-            if let ValueKind::Call(call) = value.kind() {
-                func.log_arg_num(call.args().len());
+            // log number of stack arguments, calls replaced with
+            // F extension instructions are not calls
+            match data.kind() {
+                ValueKind::Call(call) if abi.is_call(data.kind()) => {
+                    let args = abi.args(call);
+                    let stack = args.iter().filter(|loc| matches!(loc, ArgLoc::Stack(_)));
+                    func.log_stack_args(stack.count());
+                }
+                _ => {}
             }
         }
 
         // generate basic block names
-        for (&bb, data) in self.dfg().bbs() {
-            // basic block parameters are not supported
-            assert!(data.params().is_empty());
-            func.log_bb_name(bb, data.name());
+        for &bb in self.layout().bbs().keys() {
+            func.log_bb_name(bb, self.dfg().bb(bb).name());
         }
 
-        // generate prologue
-        AsmBuilder::new(f, "t0").prologue(self.name(), func)?;
-
-        // generate instructions in basic blocks
-        for (bb, node) in self.layout().bbs() {
-            let name = bb.generate(f, info)?;
-            writeln!(f, "{name}:")?;
-            for &inst in node.insts().keys() {
-                self.dfg().value(inst).generate(f, info)?;
-            }
+        // generate with short branches first, regenerate with long branches
+        // if the function is out of the range of conditional branches (±4 KiB)
+        let mut code = Vec::new();
+        generate_func_code(&mut code, info, self)?;
+        if code_size(&code) >= 4096 {
+            cur_func_mut!(info).set_long_branch();
+            code.clear();
+            generate_func_code(&mut code, info, self)?;
         }
-        Ok(())
+        f.write_all(&code)
     }
 }  
 
 impl<'p, 'i> GenerateToAsm<'p, 'i> for BasicBlock {
     type Out = &'i str;
   
-    fn generate(&self, _: &mut File, info: &'i mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, _: &mut Vec<u8>, info: &'i mut ProgramInfo) -> Result<Self::Out> {
         Ok(cur_func!(info).bb_name(*self))
     }
 }
@@ -110,67 +132,246 @@ impl<'p, 'i> GenerateToAsm<'p, 'i> for BasicBlock {
 impl<'p, 'i> GenerateToAsm<'p, 'i> for ValueData {
     type Out = ();
   
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
         match self.kind() {
+            ValueKind::Alloc(_) => Ok(()),
             ValueKind::Load(v) => v.generate(f, info, self),
             ValueKind::Store(v) => v.generate(f, info),
+            ValueKind::GetPtr(v) => v.generate(f, info, self),
+            ValueKind::GetElemPtr(v) => v.generate(f, info, self),
+            ValueKind::Binary(v) => v.generate(f, info, self),
+            ValueKind::Branch(v) => v.generate(f, info),
             ValueKind::Jump(v) => v.generate(f, info),
+            ValueKind::Call(v) => v.generate(f, info, self),
             ValueKind::Return(v) => v.generate(f, info),
-            _ => Ok(()),
+            ValueKind::GlobalAlloc(v) => v.generate(f, info),
+            ValueKind::Integer(v) => writeln!(f, "  .word {}", v.value()),
+            ValueKind::ZeroInit(_) => writeln!(f, "  .zero {}", self.ty().size()),
+            ValueKind::Aggregate(v) => v.generate(f, info),
+            kind => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported instruction: {kind:?}"),
+            )),
         }
     }
 }
 
+impl<'p, 'i> GenerateToAsm<'p, 'i> for GlobalAlloc {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
+        info.program().borrow_value(self.init()).generate(f, info)
+    }
+}
+
+impl<'p, 'i> GenerateToAsm<'p, 'i> for Aggregate {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
+        for &elem in self.elems() {
+            info.program().borrow_value(elem).generate(f, info)?;
+        }
+        Ok(())
+    }
+}
+
 impl<'p, 'i> GenerateValueToAsm<'p, 'i> for Load {
     type Out = ();
   
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+        let dest = asm_value!(info, v);
+        let reg = dest.dest_reg("t0");
         let src = self.src().generate(f, info)?;
-        src.write_to(f, "t0")?;
         if src.is_ptr() {
-            AsmBuilder::new(f, "t1").lw("t0", "t0", 0)?;
+            let addr = src.into_reg(f, "t0")?;
+            AsmBuilder::new(f, "t1").lw(reg, addr, 0)?;
+        } else {
+            src.write_to(f, reg)?;
         }
-        asm_value!(info, v).read_from(f, "t0", "t1")
+        dest.read_from(f, reg, "t1")
     }
 }
 
 impl<'p, 'i> GenerateToAsm<'p, 'i> for Store {
     type Out = ();
 
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
-        let sp_offset = cur_func!(info).sp_offset();
-        let value = self.value().generate(f, info)?;
-        if matches!(value, AsmValue::Arg(_)) {
-            value.write_arg_to(f, "t0", sp_offset)?;
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
+        let value = self.value().generate(f, info)?.into_reg(f, "t0")?;
+        let dest = self.dest().generate(f, info)?;
+        if dest.is_ptr() {
+            let addr = dest.into_reg(f, "t1")?;
+            AsmBuilder::new(f, "t2").sw(value, addr, 0)
         } else {
-            value.write_to(f, "t0")?;
+            dest.read_from(f, value, "t1")
         }
+    }
+}
 
-        let dest = self.dest().generate(f, info)?;
-        if dest.is_ptr() {
-            dest.write_to(f, "t1")?;
-            AsmBuilder::new(f, "t2").sw("t0", "t1", 0)
+impl<'p, 'i> GenerateValueToAsm<'p, 'i> for GetPtr {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+        let size = match info.ty(self.src()).kind() {
+            TypeKind::Pointer(base) => base.size(),
+            _ => unreachable!(),
+        };
+        let dest = asm_value!(info, v);
+        let reg = dest.dest_reg("t0");
+        generate_ptr_offset(f, info, self.src(), self.index(), size, reg)?;
+        dest.read_from(f, reg, "t1")
+    }
+}
+
+impl<'p, 'i> GenerateValueToAsm<'p, 'i> for GetElemPtr {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+        let size = match info.ty(self.src()).kind() {
+            TypeKind::Pointer(base) => match base.kind() {
+                TypeKind::Array(elem, _) => elem.size(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let dest = asm_value!(info, v);
+        let reg = dest.dest_reg("t0");
+        generate_ptr_offset(f, info, self.src(), self.index(), size, reg)?;
+        dest.read_from(f, reg, "t1")
+    }
+}
+
+impl<'p, 'i> GenerateValueToAsm<'p, 'i> for Binary {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+        let lhs = self.lhs().generate(f, info)?.into_reg(f, "t0")?;
+        let rhs = self.rhs().generate(f, info)?.into_reg(f, "t1")?;
+        let dest = asm_value!(info, v);
+        let reg = dest.dest_reg("t0");
+        let mut builder = AsmBuilder::new(f, "t2");
+        match self.op() {
+            BinaryOp::NotEq => {
+                builder.op2("xor", reg, lhs, rhs)?;
+                builder.snez(reg, reg)?;
+            }
+            BinaryOp::Eq => {
+                builder.op2("xor", reg, lhs, rhs)?;
+                builder.seqz(reg, reg)?;
+            }
+            BinaryOp::Gt => builder.op2("sgt", reg, lhs, rhs)?,
+            BinaryOp::Lt => builder.op2("slt", reg, lhs, rhs)?,
+            BinaryOp::Ge => {
+                builder.op2("slt", reg, lhs, rhs)?;
+                builder.seqz(reg, reg)?;
+            }
+            BinaryOp::Le => {
+                builder.op2("sgt", reg, lhs, rhs)?;
+                builder.seqz(reg, reg)?;
+            }
+            BinaryOp::Add => builder.op2("add", reg, lhs, rhs)?,
+            BinaryOp::Sub => builder.op2("sub", reg, lhs, rhs)?,
+            BinaryOp::Mul => builder.op2("mul", reg, lhs, rhs)?,
+            BinaryOp::Div => builder.op2("div", reg, lhs, rhs)?,
+            BinaryOp::Mod => builder.op2("rem", reg, lhs, rhs)?,
+            BinaryOp::And => builder.op2("and", reg, lhs, rhs)?,
+            BinaryOp::Or => builder.op2("or", reg, lhs, rhs)?,
+            BinaryOp::Xor => builder.op2("xor", reg, lhs, rhs)?,
+            BinaryOp::Shl => builder.op2("sll", reg, lhs, rhs)?,
+            BinaryOp::Shr => builder.op2("srl", reg, lhs, rhs)?,
+            BinaryOp::Sar => builder.op2("sra", reg, lhs, rhs)?,
+        }
+        dest.read_from(f, reg, "t1")
+    }
+}
+
+impl<'p, 'i> GenerateToAsm<'p, 'i> for Branch {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
+        let cond = self.cond().generate(f, info)?.into_reg(f, "t0")?;
+        let func = cur_func!(info);
+        if func.has_long_branch() || !self.true_args().is_empty() {
+            // `j` has a much larger range than `bnez`,
+            // and there is a place to put copies of basic block arguments
+            let skip = func.temp_label();
+            AsmBuilder::new(f, "t1").beqz(cond, &skip)?;
+            generate_bb_args(f, info, self.true_bb(), self.true_args())?;
+            let label = self.true_bb().generate(f, info)?;
+            AsmBuilder::new(f, "t1").j(label)?;
+            AsmBuilder::new(f, "t1").label(&skip)?;
         } else {
-            dest.read_from(f, "t0", "t1")
+            let label = self.true_bb().generate(f, info)?;
+            AsmBuilder::new(f, "t1").bnez(cond, label)?;
         }
+        generate_bb_args(f, info, self.false_bb(), self.false_args())?;
+        let label = self.false_bb().generate(f, info)?;
+        AsmBuilder::new(f, "t1").j(label)
     }
 }
 
 impl<'p, 'i> GenerateToAsm<'p, 'i> for Jump {
     type Out = ();
 
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
+        generate_bb_args(f, info, self.target(), self.args())?;
         let label = self.target().generate(f, info)?;
         AsmBuilder::new(f, "t0").j(label)
     }
 }  
 
+impl<'p, 'i> GenerateValueToAsm<'p, 'i> for Call {
+    type Out = ();
+
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo, v: &ValueData) -> Result<Self::Out> {
+        let abi = info.abi();
+        if let Some(op) = abi.float_op(self) {
+            return generate_float_op(f, info, op, self.args(), v);
+        }
+
+        // put arguments into registers/stack, addresses of allocations
+        // are computed last since they do not read any registers
+        let (addrs, moves): (Vec<_>, Vec<_>) = self
+            .args()
+            .iter()
+            .zip(abi.args(self))
+            .map(|(&arg, loc)| (AsmValue::from(loc), location(info, arg), is_alloc(info, arg)))
+            .partition(|(_, _, is_alloc)| *is_alloc);
+        generate_moves(f, moves.into_iter().map(|(dest, src, _)| (dest, src)).collect())?;
+        for (dest, src, _) in addrs {
+            let reg = dest.dest_reg("t0");
+            src.write_addr_to(f, reg)?;
+            dest.read_from(f, reg, "t1")?;
+        }
+
+        // generate call
+        let callee = info.program().func(self.callee());
+        AsmBuilder::new(f, "t0").call(&callee.name()[1..])?;
+
+        // store the return value
+        if !v.used_by().is_empty() {
+            let ret = match abi.result_reg(self) {
+                "fa0" => AsmValue::FReg("fa0"),
+                reg => AsmValue::Reg(reg),
+            };
+            let reg = ret.into_reg(f, "t0")?;
+            asm_value!(info, v).read_from(f, reg, "t1")?;
+        }
+        Ok(())
+    }
+}
+
 impl<'p, 'i> GenerateToAsm<'p, 'i> for Return {
     type Out = ();
 
-    fn generate(&self, f: &mut File, info: &mut ProgramInfo) -> Result<Self::Out> {
+    fn generate(&self, f: &mut Vec<u8>, info: &mut ProgramInfo) -> Result<Self::Out> {
         if let Some(value) = self.value() {
-            value.generate(f, info)?.write_to(f, "a0")?;
+            let func = info.program().func(cur_func!(info).func());
+            let ret = match info.abi().ret_reg(func) {
+                "fa0" => AsmValue::FReg("fa0"),
+                reg => AsmValue::Reg(reg),
+            };
+            let value = value.generate(f, info)?;
+            generate_moves(f, vec![(ret, value)])?;
         }
         AsmBuilder::new(f, "t0").epilogue(cur_func!(info))
     }
@@ -179,17 +380,465 @@ impl<'p, 'i> GenerateToAsm<'p, 'i> for Return {
 impl<'p, 'i> GenerateToAsm<'p, 'i> for Value {
     type Out = AsmValue<'i>;
 
-    fn generate(&self, _: &mut File, info: &'i mut ProgramInfo) -> Result<Self::Out> {
-        if self.is_global() {
-            Ok(AsmValue::Global(info.value(*self)))
-        } else {
-            let func = cur_func!(info);
-            let value = info.program().func(func.func()).dfg().value(*self);
-            Ok(match value.kind() {
-                ValueKind::Integer(i) => AsmValue::Const(i.value()),
-                ValueKind::FuncArgRef(i) => AsmValue::Arg(i.index()),
-                _ => AsmValue::from(func.slot_offset(value)),
-            })
+    fn generate(&self, _: &mut Vec<u8>, info: &'i mut ProgramInfo) -> Result<Self::Out> {
+        Ok(location(info, *self))
+    }
+}
+
+/// Returns the assembly value of the given value.
+fn location<'i>(info: &'i ProgramInfo, value: Value) -> AsmValue<'i> {
+    if value.is_global() {
+        return AsmValue::Global(info.value(value));
+    }
+    let func = cur_func!(info);
+    let data = info.program().func(func.func()).dfg().value(value);
+    match data.kind() {
+        ValueKind::Integer(i) => AsmValue::Const(i.value()),
+        _ => asm_value!(info, data),
+    }
+}
+
+/// Returns `true` if the given value is an allocation, whose address
+/// is used when it is used as an operand.
+fn is_alloc(info: &ProgramInfo, value: Value) -> bool {
+    value.is_global() || {
+        let dfg = info.program().func(cur_func!(info).func()).dfg();
+        matches!(dfg.value(value).kind(), ValueKind::Alloc(_))
+    }
+}
+
+/// Generates the prologue and the basic blocks of the given function.
+fn generate_func_code(f: &mut Vec<u8>, info: &mut ProgramInfo, func: &FunctionData) -> Result<()> {
+    // generate prologue, move arguments to their locations
+    AsmBuilder::new(f, "t0").prologue(func.name(), cur_func!(info))?;
+    let sp_offset = cur_func!(info).sp_offset();
+    let moves = func
+        .params()
+        .iter()
+        .zip(info.abi().params(func))
+        .map(|(&param, loc)| {
+            let src = match loc {
+                // passed on the stack frame of the caller
+                ArgLoc::Stack(index) => {
+                    let ty = func.dfg().value(param).ty();
+                    let is_ptr = matches!(ty.kind(), TypeKind::Pointer(_));
+                    AsmValue::Local(Slot::new(sp_offset + index * 4, is_ptr))
+                }
+                loc => AsmValue::from(loc),
+            };
+            (location(info, param), src)
+        })
+        .filter(|(dest, _)| *dest != AsmValue::Void)
+        .collect();
+    generate_moves(f, moves)?;
+
+    // generate instructions in basic blocks
+    for (bb, node) in func.layout().bbs() {
+        let name = bb.generate(f, info)?;
+        writeln!(f, "{name}:")?;
+        for &inst in node.insts().keys() {
+            func.dfg().value(inst).generate(f, info)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the size in bytes of the given assembly code,
+/// pseudo instructions are counted by the size of their expansions.
+fn code_size(code: &[u8]) -> usize {
+    let insts: usize = String::from_utf8_lossy(code)
+        .lines()
+        .map(|line| {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                // labels and directives
+                None => 0,
+                Some(op) if op.ends_with(':') || op.starts_with('.') => 0,
+                // `lui` and `addi` if the immediate does not fit in 12 bits
+                Some("li") => match tokens.nth(1).and_then(|imm| imm.parse::<i32>().ok()) {
+                    Some(-2048..=2047) => 1,
+                    _ => 2,
+                },
+                // `auipc` and `addi`/`jalr`
+                Some("la" | "call") => 2,
+                Some(_) => 1,
+            }
+        })
+        .sum();
+    insts * 4
+}
+
+/// Copies the given arguments to parameters of the given basic block.
+fn generate_bb_args(
+    f: &mut Vec<u8>,
+    info: &mut ProgramInfo,
+    bb: BasicBlock,
+    args: &[Value],
+) -> Result<()> {
+    let dfg = info.program().func(cur_func!(info).func()).dfg();
+    let moves = dfg
+        .bb(bb)
+        .params()
+        .iter()
+        .zip(args)
+        .filter(|(_, &arg)| !matches!(dfg.value(arg).kind(), ValueKind::Undef(_)))
+        .map(|(&param, &arg)| (location(info, param), location(info, arg)))
+        .filter(|(dest, _)| *dest != AsmValue::Void)
+        .collect();
+    generate_moves(f, moves)
+}
+
+/// Moves values from sources to destinations in parallel,
+/// i.e. all sources are read before any destination is written.
+///
+/// Moves are in the form of `(destination, source)`.
+fn generate_moves(f: &mut Vec<u8>, mut moves: Vec<(AsmValue, AsmValue)>) -> Result<()> {
+    moves.retain(|(dest, src)| dest != src);
+    while !moves.is_empty() {
+        // find a move whose destination is not read by other moves
+        let ready = moves
+            .iter()
+            .position(|(dest, _)| moves.iter().all(|(_, src)| src != dest));
+        match ready {
+            Some(i) => {
+                let (dest, src) = moves.remove(i);
+                if let AsmValue::Reg(reg) = dest {
+                    src.write_to(f, reg)?;
+                } else {
+                    let reg = src.into_reg(f, "t0")?;
+                    dest.read_from(f, reg, "t1")?;
+                }
+            }
+            None => {
+                // all remaining moves form cycles, break one of them
+                let dest = moves[0].0;
+                dest.write_to(f, "t2")?;
+                for (_, src) in &mut moves {
+                    if *src == dest {
+                        *src = AsmValue::Reg("t2");
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generates a call to a soft-float routine as F extension instructions,
+/// operands are computed in `ft0` and `ft1`.
+fn generate_float_op(
+    f: &mut Vec<u8>,
+    info: &mut ProgramInfo,
+    op: FloatOp,
+    args: &[Value],
+    v: &ValueData,
+) -> Result<()> {
+    if v.used_by().is_empty() {
+        return Ok(());
+    }
+    if op == FloatOp::FromInt {
+        let src = args[0].generate(f, info)?.into_reg(f, "t0")?;
+        AsmBuilder::new(f, "t1").op1("fcvt.s.w", "ft0", src)?;
+        return asm_value!(info, v).read_from_freg(f, "ft0", "t1");
+    }
+    for (&arg, freg) in args.iter().zip(["ft0", "ft1"]) {
+        arg.generate(f, info)?.write_to_freg(f, freg, "t0")?;
+    }
+    let dest = asm_value!(info, v);
+    let reg = dest.dest_reg("t0");
+    let mut builder = AsmBuilder::new(f, "t1");
+    match op {
+        FloatOp::Add => builder.op2("fadd.s", "ft0", "ft0", "ft1")?,
+        FloatOp::Sub => builder.op2("fsub.s", "ft0", "ft0", "ft1")?,
+        FloatOp::Mul => builder.op2("fmul.s", "ft0", "ft0", "ft1")?,
+        FloatOp::Div => builder.op2("fdiv.s", "ft0", "ft0", "ft1")?,
+        FloatOp::Neg => builder.op1("fneg.s", "ft0", "ft0")?,
+        // results of comparisons have the same relation to 0 as the
+        // operands, and make the comparisons false if either of them is NaN
+        FloatOp::Eq | FloatOp::Ne => {
+            builder.op2("feq.s", reg, "ft0", "ft1")?;
+            builder.op2("xori", reg, reg, "1")?;
+        }
+        FloatOp::Lt => {
+            builder.op2("flt.s", reg, "ft0", "ft1")?;
+            builder.op2("sub", reg, "x0", reg)?;
+        }
+        FloatOp::Le => {
+            builder.op2("fle.s", reg, "ft0", "ft1")?;
+            builder.op2("xori", reg, reg, "1")?;
+        }
+        FloatOp::Gt => builder.op2("flt.s", reg, "ft1", "ft0")?,
+        FloatOp::Ge => {
+            builder.op2("fle.s", reg, "ft1", "ft0")?;
+            builder.addi(reg, reg, -1)?;
+        }
+        FloatOp::ToInt => builder.op2("fcvt.w.s", reg, "ft0", "rtz")?,
+        FloatOp::FromInt => unreachable!(),
+    }
+    match op {
+        FloatOp::Add | FloatOp::Sub | FloatOp::Mul | FloatOp::Div | FloatOp::Neg => {
+            dest.read_from_freg(f, "ft0", "t1")
+        }
+        _ => dest.read_from(f, reg, "t1"),
+    }
+}
+
+/// Computes `src + index * size` to the given register,
+/// where `src` is a pointer (or an allocation).
+fn generate_ptr_offset(
+    f: &mut Vec<u8>,
+    info: &mut ProgramInfo,
+    src: Value,
+    index: Value,
+    size: usize,
+    dest: &'static str,
+) -> Result<()> {
+    let src = src.generate(f, info)?;
+    let base = if src.is_ptr() {
+        src.into_reg(f, "t0")?
+    } else {
+        src.write_addr_to(f, "t0")?;
+        "t0"
+    };
+    let index = index.generate(f, info)?;
+    if let AsmValue::Const(0) = index {
+        return AsmBuilder::new(f, "t2").mv(dest, base);
+    }
+    let index = index.into_reg(f, "t1")?;
+    let mut builder = AsmBuilder::new(f, "t2");
+    builder.muli("t1", index, size as i32)?;
+    builder.op2("add", dest, base, "t1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::testing::{asm, koopa, run};
+
+    fn moves(moves: Vec<(AsmValue, AsmValue)>) -> Vec<String> {
+        let mut asm = Vec::new();
+        generate_moves(&mut asm, moves).unwrap();
+        String::from_utf8(asm).unwrap().lines().map(|l| l.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn moves_in_order() {
+        // `a1` must be read before it is overwritten
+        let asm = moves(vec![
+            (AsmValue::Reg("a1"), AsmValue::Const(1)),
+            (AsmValue::Reg("a0"), AsmValue::Reg("a1")),
+            (AsmValue::Reg("a2"), AsmValue::Reg("a2")),
+        ]);
+        assert_eq!(asm, ["mv a0, a1", "li a1, 1"]);
+    }
+
+    #[test]
+    fn moves_swap() {
+        let asm = moves(vec![
+            (AsmValue::Reg("a0"), AsmValue::Reg("a1")),
+            (AsmValue::Reg("a1"), AsmValue::Reg("a0")),
+        ]);
+        assert_eq!(asm, ["mv t2, a0", "mv a0, a1", "mv a1, t2"]);
+    }
+
+    #[test]
+    fn moves_rotation() {
+        let asm = moves(vec![
+            (AsmValue::Reg("a0"), AsmValue::Reg("a1")),
+            (AsmValue::Reg("a1"), AsmValue::Reg("a2")),
+            (AsmValue::Reg("a2"), AsmValue::Reg("a0")),
+        ]);
+        assert_eq!(asm, ["mv t2, a0", "mv a0, a1", "mv a1, a2", "mv a2, t2"]);
+    }
+
+    #[test]
+    fn moves_cycle_through_slot() {
+        let slot = AsmValue::Local(Slot::new(8, false));
+        let asm = moves(vec![(slot, AsmValue::Reg("s1")), (AsmValue::Reg("s1"), slot)]);
+        assert_eq!(asm, ["lw t2, 8(sp)", "sw s1, 8(sp)", "mv s1, t2"]);
+    }
+
+    /// Swaps and rotates values through basic block parameters.
+    const BB_PARAMS: &str = r#"
+decl @putint(i32)
+decl @putch(i32)
+
+fun @swap(%n: i32, %a0: i32, %b0: i32): i32 {
+%entry:
+  jump %loop(0, %a0, %b0)
+
+%loop(%i: i32, %a: i32, %b: i32):
+  %c = lt %i, %n
+  br %c, %body, %end(%a, %b)
+
+%body:
+  %i1 = add %i, 1
+  jump %loop(%i1, %b, %a)
+
+%end(%x: i32, %y: i32):
+  %r = mul %x, 10
+  %r1 = add %r, %y
+  ret %r1
+}
+
+fun @rot(%n: i32): i32 {
+%entry:
+  jump %loop(%n, 1, 2, 3, undef)
+
+%loop(%i: i32, %a: i32, %b: i32, %c: i32, %u: i32):
+  %z = eq %i, 0
+  br %z, %end, %loop2(%i, %b, %c, %a)
+
+%loop2(%j: i32, %p: i32, %q: i32, %r: i32):
+  %j1 = sub %j, 1
+  br %j1, %loop(%j1, %p, %q, %r, 0), %end
+
+%end:
+  %s0 = mul %a, 100
+  %s1 = mul %b, 10
+  %s2 = add %s0, %s1
+  %s3 = add %s2, %c
+  ret %s3
+}
+
+fun @main(): i32 {
+%entry:
+  %x = call @swap(3, 1, 2)
+  call @putint(%x)
+  call @putch(32)
+  %y = call @swap(4, 1, 2)
+  call @putint(%y)
+  call @putch(32)
+  %z = call @rot(1)
+  call @putint(%z)
+  call @putch(32)
+  %w = call @rot(2)
+  call @putint(%w)
+  ret 0
+}
+"#;
+
+    #[test]
+    fn bb_params() {
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            let asm = asm(&koopa(BB_PARAMS), reg_alloc);
+            assert_eq!(run(&asm, ""), ("21 12 123 231".into(), 0));
+        }
+    }
+
+    /// Returns a function whose taken branch skips `n` instructions.
+    fn skipping_branch(n: usize) -> String {
+        let mut text = "fun @main(): i32 {\n%entry:\n  %x0 = add 0, 0\n".to_string();
+        text += "  br %x0, %skipped, %end\n%skipped:\n";
+        for i in 0..n {
+            text += &format!("  %x{} = add %x{i}, 1\n", i + 1);
+        }
+        text += &format!("  ret %x{n}\n%end:\n  ret 1\n}}\n");
+        text
+    }
+
+    #[test]
+    fn code_size_of_pseudo_insts() {
+        let code = "  .text\nmain:\n  li a0, 2047\n  li a0, 2048\n  la a0, x\n  call f\n  ret\n";
+        assert_eq!(code_size(code.as_bytes()), 32);
+    }
+
+    #[test]
+    fn short_branches_in_small_function() {
+        let asm = asm(&koopa(&skipping_branch(100)), RegAlloc::None);
+        assert!(asm.contains("bnez"));
+        assert_eq!(run(&asm, ""), ("".into(), 1));
+    }
+
+    #[test]
+    fn long_branches_in_large_function() {
+        let asm = asm(&koopa(&skipping_branch(1000)), RegAlloc::None);
+        assert!(!asm.contains("bnez"));
+        assert_eq!(run(&asm, ""), ("".into(), 1));
+    }
+
+    #[test]
+    fn binary_ops() {
+        type Op = fn(i32, i32) -> i32;
+        let ops: [(&str, Op); 17] = [
+            ("ne", |a, b| (a != b) as i32),
+            ("eq", |a, b| (a == b) as i32),
+            ("gt", |a, b| (a > b) as i32),
+            ("lt", |a, b| (a < b) as i32),
+            ("ge", |a, b| (a >= b) as i32),
+            ("le", |a, b| (a <= b) as i32),
+            ("add", |a, b| a + b),
+            ("sub", |a, b| a - b),
+            ("mul", |a, b| a * b),
+            ("div", |a, b| a / b),
+            ("mod", |a, b| a % b),
+            ("and", |a, b| a & b),
+            ("or", |a, b| a | b),
+            ("xor", |a, b| a ^ b),
+            // shift amounts are masked to 5 bits
+            ("shl", |a, b| a.wrapping_shl(b as u32)),
+            ("shr", |a, b| (a as u32).wrapping_shr(b as u32) as i32),
+            ("sar", |a, b| a.wrapping_shr(b as u32)),
+        ];
+        let mut text = "decl @getint(): i32\ndecl @putint(i32)\ndecl @putch(i32)\n\
+                        fun @main(): i32 {\n%entry:\n  \
+                        %a = call @getint()\n  %b = call @getint()\n"
+            .to_string();
+        let mut expected = Vec::new();
+        for (i, (op, f)) in ops.iter().enumerate() {
+            for (j, (lhs, rhs)) in [("a", "b"), ("b", "a")].into_iter().enumerate() {
+                text += &format!("  %r{i}_{j} = {op} %{lhs}, %{rhs}\n");
+                text += &format!("  call @putint(%r{i}_{j})\n  call @putch(32)\n");
+            }
+            expected.push(f(-7, 3).to_string());
+            expected.push(f(3, -7).to_string());
+        }
+        text += "  ret 0\n}\n";
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            let asm = asm(&koopa(&text), reg_alloc);
+            let (output, _) = run(&asm, "-7 3");
+            assert_eq!(output.split_whitespace().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn alloc_args() {
+        let text = r#"
+decl @putint(i32)
+
+global @g = alloc i32, zeroinit
+
+fun @set(%p: *i32, %v: i32) {
+%entry:
+  store %v, %p
+  ret
+}
+
+fun @set9(%v0: i32, %v1: i32, %v2: i32, %v3: i32, %v4: i32, %v5: i32, %v6: i32, %v7: i32, %p: *i32) {
+%entry:
+  store %v7, %p
+  ret
+}
+
+fun @main(): i32 {
+%entry:
+  %a = alloc i32
+  %b = alloc i32
+  call @set(%a, 1)
+  call @set(@g, 2)
+  call @set9(0, 0, 0, 0, 0, 0, 0, 3, %b)
+  %x = load %a
+  %y = load @g
+  %z = load %b
+  call @putint(%x)
+  call @putint(%y)
+  call @putint(%z)
+  ret 0
+}
+"#;
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            let asm = asm(&koopa(text), reg_alloc);
+            assert_eq!(run(&asm, ""), ("123".into(), 0));
         }
     }
 }
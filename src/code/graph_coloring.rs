@@ -0,0 +1,855 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+use crate::code::abi::{Abi, ArgLoc};
+use crate::code::liveness::{is_candidate, layout_values, Liveness};
+use crate::code::values::{CALLEE_SAVED_REGS, CALLER_SAVED_REGS};
+
+/// Number of colors (allocatable registers).
+const K: usize = CALLER_SAVED_REGS.len() + CALLEE_SAVED_REGS.len();
+
+/// Allocates registers for values in the given function by
+/// iterated register coalescing (George and Appel).
+///
+/// Returns registers of values, values not in the map should be spilled.
+pub fn allocate(
+    func: &FunctionData,
+    liveness: &Liveness,
+    abi: Abi,
+) -> HashMap<Value, &'static str> {
+    let mut alloc = Allocator::new(func);
+    alloc.build(func, liveness, &loop_depths(func), abi);
+    alloc.make_worklist();
+    loop {
+        if let Some(n) = alloc.pop_simplify() {
+            alloc.simplify(n);
+        } else if let Some(m) = alloc.pop_move() {
+            alloc.coalesce(m);
+        } else if let Some(n) = alloc.pop_freeze() {
+            alloc.freeze(n);
+        } else if let Some(n) = alloc.select_spill() {
+            alloc.spill(n);
+        } else {
+            break;
+        }
+    }
+    alloc.assign_colors();
+
+    alloc
+        .values
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &value)| alloc.color[K + i].map(|c| (value, reg_name(c))))
+        .collect()
+}
+
+/// Returns the register name of the given color.
+fn reg_name(color: usize) -> &'static str {
+    if color < CALLER_SAVED_REGS.len() {
+        CALLER_SAVED_REGS[color]
+    } else {
+        CALLEE_SAVED_REGS[color - CALLER_SAVED_REGS.len()]
+    }
+}
+
+/// Returns the precolored node of the given register.
+fn reg_node(reg: &str) -> usize {
+    CALLER_SAVED_REGS
+        .iter()
+        .chain(CALLEE_SAVED_REGS.iter())
+        .position(|&r| r == reg)
+        .unwrap()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NodeState {
+    Precolored,
+    Initial,
+    Simplify,
+    Freeze,
+    Spill,
+    Coalesced,
+    Selected,
+    Colored,
+    Spilled,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MoveState {
+    Worklist,
+    Active,
+    Coalesced,
+    Constrained,
+    Frozen,
+}
+
+/// Interference graph and worklists of the allocator.
+///
+/// Nodes `0..K` are precolored registers, node `K + i` is `values[i]`.
+struct Allocator {
+    values: Vec<Value>,
+    nodes: HashMap<Value, usize>,
+    adj_set: HashSet<(usize, usize)>,
+    adj_list: Vec<Vec<usize>>,
+    degree: Vec<usize>,
+    node_state: Vec<NodeState>,
+    /// Moves in the form of `(dest, src)`.
+    moves: Vec<(usize, usize)>,
+    move_list: Vec<Vec<usize>>,
+    move_state: Vec<MoveState>,
+    alias: Vec<usize>,
+    color: Vec<Option<usize>>,
+    /// Spill costs of nodes, weighted by loop depth.
+    spill_cost: Vec<f64>,
+    // worklists may contain stale nodes/moves, check their states when popping
+    simplify_worklist: Vec<usize>,
+    freeze_worklist: Vec<usize>,
+    worklist_moves: Vec<usize>,
+    select_stack: Vec<usize>,
+}
+
+impl Allocator {
+    /// Creates a new allocator with nodes of candidate values.
+    ///
+    /// Nodes are numbered in layout order, and sets of nodes are ordered,
+    /// so the allocation is deterministic.
+    fn new(func: &FunctionData) -> Self {
+        let values: Vec<_> = layout_values(func)
+            .into_iter()
+            .filter(|&v| is_candidate(func, v))
+            .collect();
+        let nodes = values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (v, K + i))
+            .collect();
+        let n = K + values.len();
+        let mut node_state = vec![NodeState::Initial; n];
+        node_state[..K].fill(NodeState::Precolored);
+        let mut degree = vec![0; n];
+        degree[..K].fill(usize::MAX);
+        Self {
+            values,
+            nodes,
+            adj_set: HashSet::new(),
+            adj_list: vec![Vec::new(); n],
+            degree,
+            node_state,
+            moves: Vec::new(),
+            move_list: vec![Vec::new(); n],
+            move_state: Vec::new(),
+            alias: (0..n).collect(),
+            color: (0..n).map(|i| (i < K).then_some(i)).collect(),
+            spill_cost: vec![0.0; n],
+            simplify_worklist: Vec::new(),
+            freeze_worklist: Vec::new(),
+            worklist_moves: Vec::new(),
+            select_stack: Vec::new(),
+        }
+    }
+
+    /// Returns the node of the given value, `None` if the value
+    /// is not a candidate of register allocation.
+    fn node(&self, value: Value) -> Option<usize> {
+        self.nodes.get(&value).copied()
+    }
+
+    fn is_precolored(&self, n: usize) -> bool {
+        n < K
+    }
+
+    /// Builds the interference graph and collects moves.
+    fn build(
+        &mut self,
+        func: &FunctionData,
+        liveness: &Liveness,
+        depths: &HashMap<BasicBlock, u32>,
+        abi: Abi,
+    ) {
+        let dfg = func.dfg();
+        let ret_reg = abi.ret_reg(func);
+        for (&bb, node) in func.layout().bbs() {
+            let weight = 10f64.powi(depths.get(&bb).copied().unwrap_or(0).min(6) as i32);
+            let mut live: BTreeSet<_> = liveness
+                .live_out(bb)
+                .iter()
+                .filter_map(|&v| self.node(v))
+                .collect();
+
+            let insts: Vec<_> = node.insts().keys().copied().collect();
+            for &inst in insts.iter().rev() {
+                let kind = dfg.value(inst).kind();
+                match kind {
+                    // parameters of targets are defined by copies from arguments
+                    ValueKind::Branch(br) => {
+                        self.build_copies(func, &live, br.true_bb(), br.true_args(), weight);
+                        self.build_copies(func, &live, br.false_bb(), br.false_args(), weight);
+                    }
+                    ValueKind::Jump(jump) => {
+                        self.build_copies(func, &live, jump.target(), jump.args(), weight);
+                    }
+                    ValueKind::Call(call) if abi.is_call(kind) => {
+                        // the result is moved from `a0` (or `fa0`)
+                        if let Some(r) = self.node(inst) {
+                            if abi.result_reg(call) == "a0" {
+                                self.add_move(r, reg_node("a0"));
+                            }
+                            self.def(&mut live, r, weight);
+                        }
+                        // caller-saved registers are clobbered by the call
+                        for &l in &live {
+                            for reg in CALLER_SAVED_REGS {
+                                self.add_edge(reg_node(reg), l);
+                            }
+                        }
+                        // arguments are moved to argument registers
+                        for (&arg, loc) in call.args().iter().zip(abi.args(call)) {
+                            if let (Some(a), ArgLoc::Reg(reg)) = (self.node(arg), loc) {
+                                self.add_move(reg_node(reg), a);
+                            }
+                        }
+                    }
+                    ValueKind::Return(ret) => {
+                        if let Some(v) = ret.value().and_then(|v| self.node(v)) {
+                            if ret_reg == "a0" {
+                                self.add_move(reg_node("a0"), v);
+                            }
+                        }
+                    }
+                    _ => {
+                        if let Some(d) = self.node(inst) {
+                            self.def(&mut live, d, weight);
+                        }
+                    }
+                }
+                for v in kind.value_uses() {
+                    if let Some(u) = self.node(v) {
+                        live.insert(u);
+                        self.spill_cost[u] += weight;
+                    }
+                }
+            }
+
+            // function parameters are defined at the same time by moves
+            // from their locations at the entry
+            if Some(bb) == func.layout().entry_bb() {
+                for (&param, loc) in func.params().iter().zip(abi.params(func)) {
+                    if let Some(p) = self.node(param) {
+                        if let ArgLoc::Reg(reg) = loc {
+                            self.add_move(p, reg_node(reg));
+                        }
+                        self.spill_cost[p] += weight;
+                        for &l in &live {
+                            self.add_edge(p, l);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds interferences and moves for copies from the given arguments
+    /// to parameters of the given basic block.
+    fn build_copies(
+        &mut self,
+        func: &FunctionData,
+        live: &BTreeSet<usize>,
+        bb: BasicBlock,
+        args: &[Value],
+        weight: f64,
+    ) {
+        let params: Vec<_> = func.dfg().bb(bb).params().to_vec();
+        for (&param, &arg) in params.iter().zip(args) {
+            let p = match self.node(param) {
+                Some(p) => p,
+                None => continue,
+            };
+            let a = self.node(arg);
+            if let Some(a) = a {
+                self.add_move(p, a);
+            }
+            self.spill_cost[p] += weight;
+            for &l in live {
+                if Some(l) != a {
+                    self.add_edge(p, l);
+                }
+            }
+            for &other in &params {
+                if let Some(q) = self.node(other) {
+                    self.add_edge(p, q);
+                }
+            }
+        }
+    }
+
+    /// Handles definition of node `d` when walking instructions backward.
+    fn def(&mut self, live: &mut BTreeSet<usize>, d: usize, weight: f64) {
+        live.remove(&d);
+        for &l in live.iter() {
+            self.add_edge(d, l);
+        }
+        self.spill_cost[d] += weight;
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize) {
+        if u != v && !self.adj_set.contains(&(u, v)) {
+            self.adj_set.insert((u, v));
+            self.adj_set.insert((v, u));
+            if !self.is_precolored(u) {
+                self.adj_list[u].push(v);
+                self.degree[u] += 1;
+            }
+            if !self.is_precolored(v) {
+                self.adj_list[v].push(u);
+                self.degree[v] += 1;
+            }
+        }
+    }
+
+    fn add_move(&mut self, dest: usize, src: usize) {
+        let m = self.moves.len();
+        self.moves.push((dest, src));
+        self.move_state.push(MoveState::Worklist);
+        self.move_list[dest].push(m);
+        self.move_list[src].push(m);
+        self.worklist_moves.push(m);
+    }
+
+    fn make_worklist(&mut self) {
+        for n in K..self.node_state.len() {
+            if self.degree[n] >= K {
+                self.node_state[n] = NodeState::Spill;
+            } else if self.is_move_related(n) {
+                self.push_freeze(n);
+            } else {
+                self.push_simplify(n);
+            }
+        }
+    }
+
+    fn adjacent(&self, n: usize) -> Vec<usize> {
+        self.adj_list[n]
+            .iter()
+            .copied()
+            .filter(|&m| {
+                !matches!(
+                    self.node_state[m],
+                    NodeState::Selected | NodeState::Coalesced
+                )
+            })
+            .collect()
+    }
+
+    fn node_moves(&self, n: usize) -> Vec<usize> {
+        self.move_list[n]
+            .iter()
+            .copied()
+            .filter(|&m| matches!(self.move_state[m], MoveState::Active | MoveState::Worklist))
+            .collect()
+    }
+
+    fn is_move_related(&self, n: usize) -> bool {
+        !self.node_moves(n).is_empty()
+    }
+
+    fn push_simplify(&mut self, n: usize) {
+        self.node_state[n] = NodeState::Simplify;
+        self.simplify_worklist.push(n);
+    }
+
+    fn push_freeze(&mut self, n: usize) {
+        self.node_state[n] = NodeState::Freeze;
+        self.freeze_worklist.push(n);
+    }
+
+    fn pop_simplify(&mut self) -> Option<usize> {
+        while let Some(n) = self.simplify_worklist.pop() {
+            if self.node_state[n] == NodeState::Simplify {
+                return Some(n);
+            }
+        }
+        None
+    }
+
+    fn pop_freeze(&mut self) -> Option<usize> {
+        while let Some(n) = self.freeze_worklist.pop() {
+            if self.node_state[n] == NodeState::Freeze {
+                return Some(n);
+            }
+        }
+        None
+    }
+
+    fn pop_move(&mut self) -> Option<usize> {
+        while let Some(m) = self.worklist_moves.pop() {
+            if self.move_state[m] == MoveState::Worklist {
+                return Some(m);
+            }
+        }
+        None
+    }
+
+    fn simplify(&mut self, n: usize) {
+        self.node_state[n] = NodeState::Selected;
+        self.select_stack.push(n);
+        for m in self.adjacent(n) {
+            self.decrement_degree(m);
+        }
+    }
+
+    fn decrement_degree(&mut self, m: usize) {
+        if self.is_precolored(m) {
+            return;
+        }
+        let d = self.degree[m];
+        self.degree[m] -= 1;
+        if d == K {
+            let mut nodes = self.adjacent(m);
+            nodes.push(m);
+            self.enable_moves(&nodes);
+            if self.node_state[m] == NodeState::Spill {
+                if self.is_move_related(m) {
+                    self.push_freeze(m);
+                } else {
+                    self.push_simplify(m);
+                }
+            }
+        }
+    }
+
+    fn enable_moves(&mut self, nodes: &[usize]) {
+        for &n in nodes {
+            for m in self.node_moves(n) {
+                if self.move_state[m] == MoveState::Active {
+                    self.move_state[m] = MoveState::Worklist;
+                    self.worklist_moves.push(m);
+                }
+            }
+        }
+    }
+
+    fn coalesce(&mut self, m: usize) {
+        let (x, y) = self.moves[m];
+        let (x, y) = (self.alias(x), self.alias(y));
+        let (u, v) = if self.is_precolored(y) {
+            (y, x)
+        } else {
+            (x, y)
+        };
+        if u == v {
+            self.move_state[m] = MoveState::Coalesced;
+            self.add_worklist(u);
+        } else if self.is_precolored(v) || self.adj_set.contains(&(u, v)) {
+            self.move_state[m] = MoveState::Constrained;
+            self.add_worklist(u);
+            self.add_worklist(v);
+        } else if self.can_coalesce(u, v) {
+            self.move_state[m] = MoveState::Coalesced;
+            self.combine(u, v);
+            self.add_worklist(u);
+        } else {
+            self.move_state[m] = MoveState::Active;
+        }
+    }
+
+    /// Returns `true` if coalescing `v` into `u` will not make
+    /// the graph uncolorable, by George's test if `u` is precolored,
+    /// or Briggs's test otherwise.
+    fn can_coalesce(&self, u: usize, v: usize) -> bool {
+        if self.is_precolored(u) {
+            self.adjacent(v).into_iter().all(|t| {
+                self.degree[t] < K || self.is_precolored(t) || self.adj_set.contains(&(t, u))
+            })
+        } else {
+            let mut nodes: HashSet<_> = self.adjacent(u).into_iter().collect();
+            nodes.extend(self.adjacent(v));
+            nodes.into_iter().filter(|&n| self.degree[n] >= K).count() < K
+        }
+    }
+
+    fn add_worklist(&mut self, u: usize) {
+        if !self.is_precolored(u) && !self.is_move_related(u) && self.degree[u] < K {
+            self.push_simplify(u);
+        }
+    }
+
+    fn alias(&self, mut n: usize) -> usize {
+        while self.node_state[n] == NodeState::Coalesced {
+            n = self.alias[n];
+        }
+        n
+    }
+
+    fn combine(&mut self, u: usize, v: usize) {
+        self.node_state[v] = NodeState::Coalesced;
+        self.alias[v] = u;
+        let moves = std::mem::take(&mut self.move_list[v]);
+        self.move_list[u].extend(&moves);
+        self.move_list[v] = moves;
+        self.enable_moves(&[v]);
+        for t in self.adjacent(v) {
+            self.add_edge(t, u);
+            self.decrement_degree(t);
+        }
+        if self.degree[u] >= K && self.node_state[u] == NodeState::Freeze {
+            self.node_state[u] = NodeState::Spill;
+        }
+    }
+
+    fn freeze(&mut self, u: usize) {
+        self.push_simplify(u);
+        self.freeze_moves(u);
+    }
+
+    fn freeze_moves(&mut self, u: usize) {
+        for m in self.node_moves(u) {
+            let (x, y) = self.moves[m];
+            let v = if self.alias(y) == self.alias(u) {
+                self.alias(x)
+            } else {
+                self.alias(y)
+            };
+            self.move_state[m] = MoveState::Frozen;
+            if self.node_state[v] == NodeState::Freeze && !self.is_move_related(v) {
+                self.push_simplify(v);
+            }
+        }
+    }
+
+    /// Selects a node to spill, the one with the lowest cost per degree.
+    fn select_spill(&self) -> Option<usize> {
+        (K..self.node_state.len())
+            .filter(|&n| self.node_state[n] == NodeState::Spill)
+            .min_by(|&a, &b| {
+                let cost = |n: usize| self.spill_cost[n] / self.degree[n] as f64;
+                cost(a).total_cmp(&cost(b))
+            })
+    }
+
+    fn spill(&mut self, n: usize) {
+        self.push_simplify(n);
+        self.freeze_moves(n);
+    }
+
+    fn assign_colors(&mut self) {
+        while let Some(n) = self.select_stack.pop() {
+            let mut ok_colors = [true; K];
+            for &w in &self.adj_list[n] {
+                let w = self.alias(w);
+                if matches!(
+                    self.node_state[w],
+                    NodeState::Colored | NodeState::Precolored
+                ) {
+                    ok_colors[self.color[w].unwrap()] = false;
+                }
+            }
+            match ok_colors.iter().position(|&ok| ok) {
+                Some(c) => {
+                    self.node_state[n] = NodeState::Colored;
+                    self.color[n] = Some(c);
+                }
+                None => self.node_state[n] = NodeState::Spilled,
+            }
+        }
+        for n in K..self.node_state.len() {
+            if self.node_state[n] == NodeState::Coalesced {
+                self.color[n] = self.color[self.alias(n)];
+            }
+        }
+    }
+}
+
+/// Returns loop depths of basic blocks in the given function,
+/// basic blocks not in any loop are not in the map.
+fn loop_depths(func: &FunctionData) -> HashMap<BasicBlock, u32> {
+    let dfg = func.dfg();
+    let succs = |bb: BasicBlock| {
+        let node = func.layout().bbs().node(&bb).unwrap();
+        let last = node.insts().back_key().unwrap();
+        dfg.value(*last).kind().bb_uses().collect::<Vec<_>>()
+    };
+
+    // number reachable basic blocks in reverse post-order
+    let entry = func.layout().entry_bb().unwrap();
+    let mut post_order = Vec::new();
+    let mut visited = HashSet::from([entry]);
+    let mut stack = vec![(entry, succs(entry), 0)];
+    while let Some((bb, next, i)) = stack.last_mut() {
+        if let Some(&succ) = next.get(*i) {
+            *i += 1;
+            if visited.insert(succ) {
+                stack.push((succ, succs(succ), 0));
+            }
+        } else {
+            post_order.push(*bb);
+            stack.pop();
+        }
+    }
+    let rpo: Vec<_> = post_order.into_iter().rev().collect();
+    let index: HashMap<_, _> = rpo.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+    let mut preds = vec![Vec::new(); rpo.len()];
+    for (i, &bb) in rpo.iter().enumerate() {
+        for succ in succs(bb) {
+            preds[index[&succ]].push(i);
+        }
+    }
+
+    // compute immediate dominators (Cooper, Harvey and Kennedy)
+    let mut idom = vec![None; rpo.len()];
+    idom[0] = Some(0);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in 1..rpo.len() {
+            let mut new_idom = None;
+            for &p in &preds[b] {
+                if idom[p].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => p,
+                    Some(mut q) => {
+                        let mut p = p;
+                        while p != q {
+                            while p > q {
+                                p = idom[p].unwrap();
+                            }
+                            while q > p {
+                                q = idom[q].unwrap();
+                            }
+                        }
+                        p
+                    }
+                });
+            }
+            if idom[b] != new_idom {
+                idom[b] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    let dominates = |h: usize, mut b: usize| loop {
+        if b == h {
+            return true;
+        }
+        if b == 0 {
+            return false;
+        }
+        b = idom[b].unwrap();
+    };
+
+    // collect bodies of natural loops, loops with the same header are merged
+    let mut loops: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (b, ps) in preds.iter().enumerate() {
+        for &p in ps {
+            if dominates(b, p) {
+                let body = loops.entry(b).or_insert_with(|| HashSet::from([b]));
+                let mut stack = vec![p];
+                while let Some(n) = stack.pop() {
+                    if body.insert(n) {
+                        stack.extend(&preds[n]);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut depths = HashMap::new();
+    for body in loops.values() {
+        for &n in body {
+            *depths.entry(rpo[n]).or_insert(0) += 1;
+        }
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::testing::{abi, func, koopa, value};
+
+    /// Returns a function with `n` values `%v0`, `%v1`, ..., all used.
+    fn values_func(n: usize) -> String {
+        let mut text = "fun @f(): i32 {\n%entry:\n".to_string();
+        for i in 0..n {
+            text += &format!("  %v{i} = add 0, {i}\n");
+        }
+        text += "  %s0 = add %v0, 0\n";
+        for i in 1..n {
+            text += &format!("  %s{i} = add %s{}, %v{i}\n", i - 1);
+        }
+        text + &format!("  ret %s{}\n}}\n", n - 1)
+    }
+
+    #[test]
+    fn coalesce_copies() {
+        // `%x`, `%y` and `%z` are connected by moves and do not interfere
+        let program = koopa(
+            r#"
+fun @f(%x: i32): i32 {
+%entry:
+  jump %bb(%x)
+
+%bb(%y: i32):
+  %z = add %y, 1
+  jump %end(%z)
+
+%end(%w: i32):
+  ret %w
+}
+"#,
+        );
+        let func = func(&program, "f");
+        let abi = abi(&program);
+        let regs = allocate(func, &Liveness::analyze(func, abi), abi);
+        for name in ["x", "y", "z", "w"] {
+            assert_eq!(regs[&value(func, name)], "a0");
+        }
+    }
+
+    #[test]
+    fn briggs() {
+        let program = koopa(&values_func(K + 2));
+        let func = func(&program, "f");
+        let mut alloc = Allocator::new(func);
+        let node = |i: usize| alloc.node(value(func, &format!("v{i}"))).unwrap();
+        let (u, v) = (node(K), node(K + 1));
+        let high: Vec<_> = (0..K).map(node).collect();
+
+        // `K` nodes of degree `K` are adjacent to `u` or `v`
+        for (i, &a) in high.iter().enumerate() {
+            for &b in &high[i + 1..] {
+                alloc.add_edge(a, b);
+            }
+            alloc.add_edge(a, if i % 2 == 0 { u } else { v });
+        }
+        assert!(!alloc.can_coalesce(u, v));
+
+        // one of them is no longer significant
+        alloc.degree[high[0]] -= 1;
+        assert!(alloc.can_coalesce(u, v));
+    }
+
+    #[test]
+    fn george() {
+        let program = koopa(&values_func(K + 2));
+        let func = func(&program, "f");
+        let mut alloc = Allocator::new(func);
+        let node = |i: usize| alloc.node(value(func, &format!("v{i}"))).unwrap();
+        let (v, t) = (node(0), node(1));
+        let others: Vec<_> = (2..K + 2).map(node).collect();
+        let u = reg_node("a0");
+
+        // `t` is a low-degree neighbor of `v`
+        alloc.add_edge(v, t);
+        assert!(alloc.can_coalesce(u, v));
+
+        // `t` becomes significant and does not interfere with `a0`
+        for &n in &others {
+            alloc.add_edge(t, n);
+        }
+        assert!(!alloc.can_coalesce(u, v));
+
+        // `t` interferes with `a0`
+        alloc.add_edge(t, u);
+        assert!(alloc.can_coalesce(u, v));
+    }
+
+    /// Nested loops, `%x` is used in the outer loop, `%y` in the inner one.
+    const LOOPS: &str = r#"
+decl @getint(): i32
+
+fun @f(): i32 {
+%entry:
+  %x = call @getint()
+  %y = call @getint()
+  %z = call @getint()
+  jump %outer(0)
+
+%outer(%i: i32):
+  %c = lt %i, %x
+  br %c, %inner_entry, %end
+
+%inner_entry:
+  jump %inner(0)
+
+%inner(%j: i32):
+  %d = lt %j, %y
+  br %d, %inner_body, %outer_next
+
+%inner_body:
+  %j1 = add %j, 1
+  jump %inner(%j1)
+
+%outer_next:
+  %i1 = add %i, 1
+  jump %outer(%i1)
+
+%end:
+  ret %z
+}
+"#;
+
+    #[test]
+    fn loop_depths_of_nested_loops() {
+        let program = koopa(LOOPS);
+        let func = func(&program, "f");
+        let depths = loop_depths(func);
+        let depth = |name: &str| {
+            let bb = *func
+                .dfg()
+                .bbs()
+                .iter()
+                .find(|(_, data)| data.name().as_deref() == Some(name))
+                .unwrap()
+                .0;
+            depths.get(&bb).copied().unwrap_or(0)
+        };
+        assert_eq!(depth("%entry"), 0);
+        assert_eq!(depth("%outer"), 1);
+        assert_eq!(depth("%inner_entry"), 1);
+        assert_eq!(depth("%inner"), 2);
+        assert_eq!(depth("%inner_body"), 2);
+        assert_eq!(depth("%outer_next"), 1);
+        assert_eq!(depth("%end"), 0);
+    }
+
+    #[test]
+    fn spill_cost_weighted_by_loop_depth() {
+        let program = koopa(LOOPS);
+        let func = func(&program, "f");
+        let mut alloc = Allocator::new(func);
+        let abi = abi(&program);
+        alloc.build(func, &Liveness::analyze(func, abi), &loop_depths(func), abi);
+        let cost = |name| alloc.spill_cost[alloc.node(value(func, name)).unwrap()];
+
+        // one definition outside loops, plus one use in a loop
+        assert_eq!(cost("x"), 1.0 + 10.0);
+        assert_eq!(cost("y"), 1.0 + 100.0);
+        assert_eq!(cost("z"), 1.0 + 1.0);
+    }
+
+    #[test]
+    fn spill_cheapest() {
+        // up to `K + 1` values are live in the loop, `%cold` is only used after it
+        let mut text = "fun @f(%n: i32): i32 {\n%entry:\n  %cold = add %n, 1\n".to_string();
+        for i in 0..K - 1 {
+            text += &format!("  %v{i} = add %n, {i}\n");
+        }
+        text += "  jump %loop\n\n%loop:\n  %s0 = add %v0, 0\n";
+        for i in 1..K - 1 {
+            text += &format!("  %s{i} = add %s{}, %v{i}\n", i - 1);
+        }
+        text += &format!("  br %s{}, %loop, %end\n\n%end:\n  ret %cold\n}}\n", K - 2);
+
+        let program = koopa(&text);
+        let func = func(&program, "f");
+        let abi = abi(&program);
+        let regs = allocate(func, &Liveness::analyze(func, abi), abi);
+        assert!(!regs.contains_key(&value(func, "cold")));
+        for i in 0..K - 1 {
+            assert!(regs.contains_key(&value(func, &format!("v{i}"))));
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use std::collections::HashMap;
 
-use koopa::ir::{Program, Value};
+use koopa::ir::{Program, Type, Value};
 
+use crate::code::abi::Abi;
 use crate::code::func::FunctionInfo;
+use crate::code::RegAlloc;
+use crate::ir::FloatSigs;
 
 /// Some necessary information during assembly generation.
 pub struct ProgramInfo<'p> {
     program: &'p Program,
+    abi: Abi<'p>,
     values: HashMap<Value, String>,
     cur_func: Option<FunctionInfo>,
+    reg_alloc: RegAlloc,
 }
 
 /// Returns a reference to the current function information.
@@ -29,11 +34,13 @@ pub(crate) use cur_func_mut;
 
 impl<'p> ProgramInfo<'p> {
     /// Creates a new program information.
-    pub fn new(program: &'p Program) -> Self {
+    pub fn new(program: &'p Program, float_sigs: &'p FloatSigs, reg_alloc: RegAlloc) -> Self {
         Self {
             program,
+            abi: Abi::new(program, float_sigs),
             values: HashMap::new(),
             cur_func: None,
+            reg_alloc,
         }
     }
   
@@ -42,11 +49,31 @@ impl<'p> ProgramInfo<'p> {
         self.program
     }
   
+    /// Returns the calling convention.
+    pub fn abi(&self) -> Abi<'p> {
+        self.abi
+    }
+  
+    /// Returns the register allocator.
+    pub fn reg_alloc(&self) -> RegAlloc {
+        self.reg_alloc
+    }
+  
     /// Returns the name of the given global value.
     pub fn value(&self, value: Value) -> &str {
         self.values.get(&value).unwrap()
     }
   
+    /// Returns the type of the given value.
+    pub fn ty(&self, value: Value) -> Type {
+        if value.is_global() {
+            self.program.borrow_value(value).ty().clone()
+        } else {
+            let func = self.program.func(cur_func!(self).func());
+            func.dfg().value(value).ty().clone()
+        }
+    }
+
     /// Inserts a new global value name.
     pub fn insert_value(&mut self, value: Value, name: String) {
         self.values.insert(value, name);
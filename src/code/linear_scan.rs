@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use koopa::ir::{FunctionData, Value, ValueKind};
+
+use crate::code::abi::{Abi, ArgLoc};
+use crate::code::liveness::{Interval, Liveness};
+use crate::code::values::{CALLEE_SAVED_REGS, CALLER_SAVED_REGS};
+
+/// Allocates registers for values in the given function by linear scan.
+///
+/// Returns registers of values, values not in the map should be spilled.
+pub fn allocate(
+    func: &FunctionData,
+    liveness: &Liveness,
+    abi: Abi,
+) -> HashMap<Value, &'static str> {
+    let params = abi.params(func);
+    let mut regs = HashMap::new();
+    let mut active: Vec<(Interval, &'static str)> = Vec::new();
+    for &interval in liveness.intervals() {
+        // expire intervals that end before the current one
+        active.retain(|(i, _)| i.end >= interval.start);
+
+        // values live across calls can only be in callee-saved registers
+        let candidates: Vec<_> = if liveness.crosses_call(&interval) {
+            CALLEE_SAVED_REGS.to_vec()
+        } else {
+            // prefer the register that passes the argument
+            let hint = match func.dfg().value(interval.value).kind() {
+                ValueKind::FuncArgRef(arg) => match params[arg.index()] {
+                    ArgLoc::Reg(reg) => Some(reg),
+                    _ => None,
+                },
+                _ => None,
+            };
+            hint.into_iter()
+                .chain(CALLER_SAVED_REGS)
+                .chain(CALLEE_SAVED_REGS)
+                .collect()
+        };
+
+        let free = candidates
+            .iter()
+            .find(|&&reg| active.iter().all(|&(_, r)| r != reg));
+        if let Some(&reg) = free {
+            regs.insert(interval.value, reg);
+            active.push((interval, reg));
+            continue;
+        }
+
+        // spill the interval that ends last
+        let spill = active
+            .iter_mut()
+            .filter(|(_, reg)| candidates.contains(reg))
+            .max_by_key(|(i, _)| i.end);
+        if let Some((i, reg)) = spill {
+            if i.end > interval.end {
+                regs.remove(&i.value);
+                regs.insert(interval.value, *reg);
+                *i = interval;
+            }
+        }
+    }
+    regs
+}
+
+#[cfg(test)]
+mod tests {
+    use koopa::ir::Program;
+
+    use super::*;
+    use crate::code::testing::{abi, func, koopa, value};
+
+    fn allocate_func(program: &Program, name: &str) -> HashMap<Value, &'static str> {
+        let func = func(program, name);
+        let abi = abi(program);
+        allocate(func, &Liveness::analyze(func, abi), abi)
+    }
+
+    #[test]
+    fn callee_saved_across_call() {
+        let program = koopa(
+            r#"
+decl @f()
+
+fun @g(%x: i32): i32 {
+%entry:
+  %a = add %x, 1
+  call @f()
+  %b = add %a, 2
+  ret %b
+}
+"#,
+        );
+        let regs = allocate_func(&program, "g");
+        let reg = |name| regs[&value(func(&program, "g"), name)];
+        assert!(CALLEE_SAVED_REGS.contains(&reg("a")));
+        assert_eq!(reg("x"), "a0");
+        assert!(CALLER_SAVED_REGS.contains(&reg("b")));
+    }
+
+    #[test]
+    fn spill_furthest_end() {
+        // 25 values are live at the same time, `%v0` is used last
+        let n = CALLER_SAVED_REGS.len() + CALLEE_SAVED_REGS.len() + 1;
+        let mut text = "fun @f(%x: i32): i32 {\n%entry:\n".to_string();
+        for i in 0..n {
+            text += &format!("  %v{i} = add %x, {i}\n");
+        }
+        text += &format!("  %s{} = add %v{}, 0\n", n - 1, n - 1);
+        for i in (0..n - 1).rev() {
+            text += &format!("  %s{i} = add %s{}, %v{i}\n", i + 1);
+        }
+        text += "  ret %s0\n}\n";
+
+        let program = koopa(&text);
+        let func = func(&program, "f");
+        let regs = allocate_func(&program, "f");
+        assert!(!regs.contains_key(&value(func, "v0")));
+        for i in 1..n {
+            assert!(regs.contains_key(&value(func, &format!("v{i}"))));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+use crate::code::abi::Abi;
+
+/// Liveness information of values in a function.
+///
+/// Each basic block header and each instruction occupies two positions:
+/// values are used at the even one and defined at the odd one.
+pub struct Liveness {
+    /// Values live at the exit of each basic block.
+    live_out: HashMap<BasicBlock, HashSet<Value>>,
+    /// Live intervals of values, sorted by start position.
+    intervals: Vec<Interval>,
+    /// Positions of call instructions, in ascending order.
+    ///
+    /// Calls replaced with F extension instructions are not included.
+    calls: Vec<usize>,
+}
+
+/// Live interval of a value, covers positions in `[start, end]`.
+#[derive(Clone, Copy)]
+pub struct Interval {
+    pub value: Value,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Liveness {
+    /// Analyzes liveness of values in the given function.
+    pub fn analyze(func: &FunctionData, abi: Abi) -> Self {
+        let dfg = func.dfg();
+
+        // collect values used before defined, values defined
+        // and successors of each basic block
+        let mut uses = HashMap::new();
+        let mut defs = HashMap::new();
+        let mut succs = HashMap::new();
+        for (&bb, node) in func.layout().bbs() {
+            let mut used = HashSet::new();
+            let mut defined: HashSet<_> = dfg.bb(bb).params().iter().copied().collect();
+            if Some(bb) == func.layout().entry_bb() {
+                defined.extend(func.params());
+            }
+            for &inst in node.insts().keys() {
+                for v in dfg.value(inst).kind().value_uses() {
+                    if is_candidate(func, v) && !defined.contains(&v) {
+                        used.insert(v);
+                    }
+                }
+                defined.insert(inst);
+            }
+            let last = node.insts().back_key().unwrap();
+            succs.insert(bb, dfg.value(*last).kind().bb_uses().collect::<Vec<_>>());
+            uses.insert(bb, used);
+            defs.insert(bb, defined);
+        }
+
+        // compute live-in and live-out sets until reaching the fixed point
+        let bbs: Vec<_> = func.layout().bbs().keys().copied().collect();
+        let mut live_in: HashMap<_, HashSet<_>> = HashMap::new();
+        let mut live_out: HashMap<_, HashSet<_>> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for bb in bbs.iter().rev() {
+                let out: HashSet<_> = succs[bb]
+                    .iter()
+                    .flat_map(|succ| live_in.get(succ).into_iter().flatten().copied())
+                    .collect();
+                let mut cur_in = uses[bb].clone();
+                cur_in.extend(out.difference(&defs[bb]).copied());
+                if live_in.get(bb) != Some(&cur_in) {
+                    live_in.insert(*bb, cur_in);
+                    changed = true;
+                }
+                live_out.insert(*bb, out);
+            }
+        }
+
+        // build live intervals
+        let mut ranges: HashMap<Value, (usize, usize)> = HashMap::new();
+        let mut extend = |v: Value, pos: usize| {
+            if is_candidate(func, v) {
+                let range = ranges.entry(v).or_insert((pos, pos));
+                range.0 = range.0.min(pos);
+                range.1 = range.1.max(pos);
+            }
+        };
+        let mut calls = Vec::new();
+        let mut pos = 0;
+        for (&bb, node) in func.layout().bbs() {
+            // basic block header
+            live_in[&bb].iter().for_each(|&v| extend(v, pos));
+            dfg.bb(bb).params().iter().for_each(|&p| extend(p, pos + 1));
+            if Some(bb) == func.layout().entry_bb() {
+                func.params().iter().for_each(|&p| extend(p, pos));
+            }
+            pos += 2;
+
+            // instructions
+            for &inst in node.insts().keys() {
+                let kind = dfg.value(inst).kind();
+                kind.value_uses().for_each(|v| extend(v, pos));
+                extend(inst, pos + 1);
+                if abi.is_call(kind) {
+                    calls.push(pos + 1);
+                }
+                // parameters of targets are written by copies at branch sites
+                for target in kind.bb_uses() {
+                    dfg.bb(target)
+                        .params()
+                        .iter()
+                        .for_each(|&p| extend(p, pos + 1));
+                }
+                pos += 2;
+            }
+            live_out[&bb].iter().for_each(|&v| extend(v, pos - 1));
+        }
+
+        // sort by start position, and break ties by layout order to make
+        // the allocation deterministic
+        let mut intervals: Vec<_> = layout_values(func)
+            .into_iter()
+            .filter_map(|value| {
+                let &(start, end) = ranges.get(&value)?;
+                Some(Interval { value, start, end })
+            })
+            .collect();
+        intervals.sort_by_key(|i| (i.start, i.end));
+        Self {
+            live_out,
+            intervals,
+            calls,
+        }
+    }
+
+    /// Returns values live at the exit of the given basic block.
+    pub fn live_out(&self, bb: BasicBlock) -> &HashSet<Value> {
+        &self.live_out[&bb]
+    }
+
+    /// Returns live intervals of values, sorted by start position.
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    /// Returns `true` if the given interval is live across any call.
+    pub fn crosses_call(&self, interval: &Interval) -> bool {
+        let i = self.calls.partition_point(|&c| c <= interval.start);
+        self.calls.get(i).is_some_and(|&c| c < interval.end)
+    }
+}
+
+/// Returns `true` if the given value can be placed in a register.
+///
+/// Allocations and constants stay in memory.
+pub fn is_candidate(func: &FunctionData, value: Value) -> bool {
+    if value.is_global() {
+        return false;
+    }
+    let data = func.dfg().value(value);
+    !data.used_by().is_empty()
+        && match data.kind() {
+            ValueKind::FuncArgRef(_) | ValueKind::BlockArgRef(_) => true,
+            ValueKind::Alloc(_) => false,
+            kind => kind.is_local_inst() && !data.ty().is_unit(),
+        }
+}
+
+/// Returns parameters and instructions of the given function in layout order.
+pub fn layout_values(func: &FunctionData) -> Vec<Value> {
+    let mut values = func.params().to_vec();
+    for (&bb, node) in func.layout().bbs() {
+        values.extend(func.dfg().bb(bb).params());
+        values.extend(node.insts().keys());
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::testing::{abi, func, koopa, value};
+
+    #[test]
+    fn crosses_call() {
+        let program = koopa(
+            r#"
+decl @f(i32): i32
+
+fun @g(%x: i32): i32 {
+%entry:
+  %a = add %x, 1
+  %b = add %x, 2
+  %c = call @f(%a)
+  %d = add %b, %c
+  ret %d
+}
+"#,
+        );
+        let func = func(&program, "g");
+        let liveness = Liveness::analyze(func, abi(&program));
+        let interval = |name| {
+            let v = value(func, name);
+            *liveness.intervals().iter().find(|i| i.value == v).unwrap()
+        };
+        let crosses = |name| liveness.crosses_call(&interval(name));
+
+        // `%a` dies at the call, `%c` is defined by the call
+        assert_eq!((interval("a").start, interval("a").end), (3, 6));
+        assert_eq!((interval("c").start, interval("c").end), (7, 8));
+        assert!(!crosses("x"));
+        assert!(!crosses("a"));
+        assert!(crosses("b"));
+        assert!(!crosses("c"));
+        assert!(!crosses("d"));
+    }
+
+    #[test]
+    fn intervals_in_layout_order() {
+        let program = koopa(
+            r#"
+fun @f(%x: i32): i32 {
+%entry:
+  jump %bb(1, 2, 3, 4)
+
+%bb(%p: i32, %q: i32, %r: i32, %s: i32):
+  %a = add %p, %q
+  %b = add %r, %s
+  %c = add %a, %b
+  ret %c
+}
+"#,
+        );
+        let func = func(&program, "f");
+        let names: Vec<_> = Liveness::analyze(func, abi(&program))
+            .intervals()
+            .iter()
+            .map(|i| func.dfg().value(i.value).name().clone().unwrap())
+            .collect();
+        assert_eq!(names, ["%p", "%q", "%r", "%s", "%a", "%b", "%c"]);
+    }
+}
@@ -1,19 +1,154 @@
+mod abi;
 mod builder;
 mod info;
 mod func;
 mod gen;
+mod graph_coloring;
+mod linear_scan;
+mod liveness;
 mod values;
 
-use std::fs::File;
+#[cfg(test)]
+pub mod testing;
+
+use std::fs;
 use std::io::Result;
 
 use koopa::ir::{Program, Type};
 
+use crate::ir::FloatSigs;
 use info::ProgramInfo;
 use gen::GenerateToAsm;
 
-/// Generates the given Koopa IR program to RISC-V assembly.
-pub fn generate_asm(program: &Program, path: &str) -> Result<()> {
+/// Generates the given Koopa IR program to RISC-V assembly,
+/// using the given register allocator.
+///
+/// `float_sigs` tells which parameters and return values are `float`s,
+/// since they are represented by their bit patterns in Koopa IR.
+pub fn generate_asm(
+    program: &Program,
+    float_sigs: &FloatSigs,
+    path: &str,
+    reg_alloc: RegAlloc,
+) -> Result<()> {
     Type::set_ptr_size(4);
-    program.generate(&mut File::create(path)?, &mut ProgramInfo::new(program))
+    let mut info = ProgramInfo::new(program, float_sigs, reg_alloc);
+    let mut asm = Vec::new();
+    program.generate(&mut asm, &mut info)?;
+    fs::write(path, asm)
+}
+
+/// Register allocator.
+#[derive(Clone, Copy)]
+pub enum RegAlloc {
+    /// Keeps all values in stack slots.
+    None,
+    /// Linear scan, fast but produces less efficient code.
+    LinearScan,
+    /// Iterated register coalescing, slower but coalesces moves
+    /// and makes better spill decisions.
+    GraphColoring,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::testing::{compile, run};
+
+    /// Recursion, calls with stack arguments, arrays, loops and
+    /// expressions with more live temporaries than registers.
+    const PROGRAM: &str = r#"
+int g[4] = {1, 2, 3, 4};
+
+int id(int x) { return x; }
+
+int many(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j, int k) {
+  if (a > 0) return many(b, c, d, e, f, g, h, i, j, k, a - 1) + a * k;
+  return a + b * 2 + c * 3 + d - e + f - g + h - i + j - k;
+}
+
+int fib(int n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+
+int sum(int a[], int n) {
+  int i = 0, s = 0;
+  while (i < n) {
+    if (a[i] % 3 == 0) {
+      i = i + 1;
+      continue;
+    }
+    s = s + a[i];
+    i = i + 1;
+  }
+  return s;
+}
+
+int main() {
+  int v0 = getint(), v1 = getint(), v2 = getint(), v3 = getint(), v4 = getint();
+  int v5 = getint(), v6 = getint(), v7 = getint(), v8 = getint(), v9 = getint();
+  int r = v0 + (v1 - (v2 + id(v3 * (v4 - (v5 + (v6 * (v7 - (v8 + (v9 + (v0 - (v1 + (v2
+    + (v3 - (v4 + (v5 + (v6 - (v7 + (v8 + (v9 - (v0 + (v1 + (v2 - (v3 + (v4 + (v5 - (v6
+    + id(v7 + (v8 - v9))))))))))))))))))))))))))));
+  putint(r);
+  putch(32);
+  putint(many(3, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+  putch(32);
+  putint(fib(12) + id(fib(6)) * fib(5));
+  putch(32);
+  int a[10], i = 0;
+  while (i < 10) {
+    a[i] = id(i) * v3 + fib(i) + g[i % 4];
+    i = i + 1;
+  }
+  putint(sum(a, 10));
+  return r % 100;
+}
+"#;
+
+    #[test]
+    fn reg_allocs_agree() {
+        let input = "3 1 4 1 5 9 2 6 5 3";
+        let expected = run(&compile(PROGRAM, RegAlloc::None), input);
+        assert_eq!(run(&compile(PROGRAM, RegAlloc::LinearScan), input), expected);
+        assert_eq!(run(&compile(PROGRAM, RegAlloc::GraphColoring), input), expected);
+    }
+
+    #[test]
+    fn local_array_init() {
+        let text = r#"
+int main() {
+  int a[10][10] = {}, b[3][4] = {1, 2, 3, 4, {5}, 6}, c[2][20] = {{1, 2}, {getint()}};
+  putint(a[9][9] + a[0][0] + b[0][0] + b[1][0] + b[2][0] + b[2][1] + b[1][1]);
+  putint(c[0][1] + c[1][0] + c[1][19]);
+  return 0;
+}
+"#;
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            assert_eq!(run(&compile(text, reg_alloc), "7"), ("129".into(), 0));
+        }
+    }
+
+    #[test]
+    fn div_rem_overflow() {
+        let text = r#"
+int main() {
+  int a = getint(), b = getint(), c = getint();
+  putint(a / b);
+  putch(32);
+  putint(a % b);
+  putch(32);
+  putint(a / c);
+  putch(32);
+  putint(a % c);
+  return 0;
+}
+"#;
+        let output = "-2147483648 0 -1 -2147483648";
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            let asm = compile(text, reg_alloc);
+            assert_eq!(run(&asm, "-2147483648 -1 0"), (output.into(), 0));
+        }
+    }
 }
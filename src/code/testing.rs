@@ -0,0 +1,352 @@
+//! Helpers for testing the backend: compiles programs and runs
+//! the generated assembly on a small RV32IMF interpreter.
+
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::fs;
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+
+use koopa::front::Driver;
+use koopa::ir::{FunctionData, Program, Value};
+
+use crate::code::abi::Abi;
+use crate::code::{generate_asm, RegAlloc};
+use crate::ir::FloatSigs;
+use crate::sysy::CompUnitParser;
+
+/// Signatures of Koopa IR programs, which have no `float`s.
+static NO_FLOAT_SIGS: LazyLock<FloatSigs> = LazyLock::new(FloatSigs::new);
+
+/// Parses the given Koopa IR program.
+pub fn koopa(text: &str) -> Program {
+    Driver::from(text).generate_program().unwrap()
+}
+
+/// Compiles the given SysY program to RISC-V assembly.
+pub fn compile(text: &str, reg_alloc: RegAlloc) -> String {
+    let comp_unit = CompUnitParser::new().parse(&mut Vec::new(), text).unwrap();
+    let (program, float_sigs, diags) = crate::ir::generate_program(&comp_unit);
+    assert!(!diags.has_errors());
+    written(|path| generate_asm(&program, &float_sigs, path, reg_alloc))
+}
+
+/// Returns the calling convention of the given Koopa IR program.
+pub fn abi(program: &Program) -> Abi<'_> {
+    Abi::new(program, &NO_FLOAT_SIGS)
+}
+
+/// Returns the function with the given name (without `@`).
+pub fn func<'p>(program: &'p Program, name: &str) -> &'p FunctionData {
+    let name = format!("@{name}");
+    program.funcs().values().find(|f| f.name() == name).unwrap()
+}
+
+/// Returns the value with the given name (without `%`) in the function.
+pub fn value(func: &FunctionData, name: &str) -> Value {
+    let name = format!("%{name}");
+    let (&value, _) = func
+        .dfg()
+        .values()
+        .iter()
+        .find(|(_, data)| data.name().as_deref() == Some(&name))
+        .unwrap();
+    value
+}
+
+/// Generates RISC-V assembly of the given Koopa IR program.
+pub fn asm(program: &Program, reg_alloc: RegAlloc) -> String {
+    written(|path| generate_asm(program, &NO_FLOAT_SIGS, path, reg_alloc))
+}
+
+/// Returns the content written to a temporary file by `write`.
+pub fn written(write: impl FnOnce(&str) -> io::Result<()>) -> String {
+    static ID: AtomicUsize = AtomicUsize::new(0);
+    let id = ID.fetch_add(1, Ordering::Relaxed);
+    let path = temp_dir().join(format!("kira-test-{}-{id}.S", process::id()));
+    let path = path.to_str().unwrap();
+    write(path).unwrap();
+    let content = fs::read_to_string(path).unwrap();
+    fs::remove_file(path).unwrap();
+    content
+}
+
+/// Runs `main` of the given assembly with the given input,
+/// returns the output and the exit code.
+pub fn run(asm: &str, input: &str) -> (String, i32) {
+    let mut machine = Machine::new(asm, input);
+    let code = machine.run();
+    (machine.output, code)
+}
+
+/// Register names, in the order of their numbers.
+const REGS: [&str; 32] = [
+    "x0", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5",
+    "t6",
+];
+
+/// Floating-point register names, in the order of their numbers.
+const FREGS: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+    "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+    "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+/// Registers that runtime library functions may clobber.
+const CLOBBERED_REGS: [&str; 14] = [
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+];
+
+const MEM_SIZE: usize = 1 << 20;
+const MAX_STEPS: usize = 10_000_000;
+
+/// RV32IMF interpreter, supports instructions emitted by the backend.
+struct Machine<'a> {
+    insts: Vec<(&'a str, Vec<&'a str>)>,
+    labels: HashMap<&'a str, usize>,
+    symbols: HashMap<&'a str, i32>,
+    regs: [i32; 32],
+    fregs: [f32; 32],
+    mem: Vec<u8>,
+    input: std::str::SplitWhitespace<'a>,
+    output: String,
+}
+
+impl<'a> Machine<'a> {
+    fn new(asm: &'a str, input: &'a str) -> Self {
+        let mut insts = Vec::new();
+        let mut labels = HashMap::new();
+        let mut symbols = HashMap::new();
+        let mut mem = vec![0; MEM_SIZE];
+        let mut data = 0x1000;
+        let mut in_text = true;
+        for line in asm.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(label) = line.strip_suffix(':') {
+                if in_text {
+                    labels.insert(label, insts.len());
+                } else {
+                    symbols.insert(label, data as i32);
+                }
+                continue;
+            }
+            let (op, args) = line.split_once(' ').unwrap_or((line, ""));
+            let args: Vec<_> = args.split(',').map(str::trim).collect();
+            match op {
+                ".text" => in_text = true,
+                ".data" => in_text = false,
+                ".globl" => {}
+                ".word" => {
+                    let word: i32 = args[0].parse().unwrap();
+                    mem[data..data + 4].copy_from_slice(&word.to_le_bytes());
+                    data += 4;
+                }
+                ".zero" => data += args[0].parse::<usize>().unwrap(),
+                _ => insts.push((op, args)),
+            }
+        }
+        let mut regs = [0; 32];
+        regs[2] = MEM_SIZE as i32;
+        regs[1] = -1;
+        Self {
+            insts,
+            labels,
+            symbols,
+            regs,
+            fregs: [0.0; 32],
+            mem,
+            input: input.split_whitespace(),
+            output: String::new(),
+        }
+    }
+
+    fn reg(&self, name: &str) -> i32 {
+        self.regs[REGS.iter().position(|&r| r == name).unwrap()]
+    }
+
+    fn set_reg(&mut self, name: &str, value: i32) {
+        let i = REGS.iter().position(|&r| r == name).unwrap();
+        if i != 0 {
+            self.regs[i] = value;
+        }
+    }
+
+    fn freg(&self, name: &str) -> f32 {
+        self.fregs[FREGS.iter().position(|&r| r == name).unwrap()]
+    }
+
+    fn set_freg(&mut self, name: &str, value: f32) {
+        self.fregs[FREGS.iter().position(|&r| r == name).unwrap()] = value;
+    }
+
+    /// Returns the address of operand `offset(reg)`.
+    fn addr(&self, operand: &str) -> usize {
+        let (offset, reg) = operand.trim_end_matches(')').split_once('(').unwrap();
+        (offset.parse::<i32>().unwrap() + self.reg(reg)) as usize
+    }
+
+    fn load(&self, addr: usize) -> i32 {
+        i32::from_le_bytes(self.mem[addr..addr + 4].try_into().unwrap())
+    }
+
+    fn store(&mut self, addr: usize, value: i32) {
+        self.mem[addr..addr + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Calls the runtime library function, returns `false` if there is no such function.
+    fn call_runtime(&mut self, func: &str) -> bool {
+        let a0 = self.reg("a0");
+        match func {
+            "getint" => {
+                let num = self.input.next().map_or(0, |s| s.parse().unwrap());
+                self.set_reg("a0", num);
+            }
+            "putint" => self.output += &a0.to_string(),
+            "putch" => self.output.push(a0 as u8 as char),
+            "getfloat" => {
+                let num = self.input.next().map_or(0.0, |s| s.parse().unwrap());
+                self.set_freg("fa0", num);
+            }
+            "putfloat" => self.output += &hex_float(self.freg("fa0")),
+            _ => return false,
+        }
+        CLOBBERED_REGS
+            .iter()
+            .for_each(|r| self.set_reg(r, 0x5a5a5a5a));
+        true
+    }
+
+    fn run(&mut self) -> i32 {
+        let mut pc = self.labels["main"];
+        for _ in 0..MAX_STEPS {
+            let (op, args) = self.insts[pc].clone();
+            pc += 1;
+            if self.float_inst(op, &args) {
+                continue;
+            }
+            let reg = |i: usize| self.reg(args[i]);
+            let imm = |i: usize| args[i].parse::<i32>().unwrap();
+            let value = match op {
+                "li" => imm(1),
+                "la" => self.symbols[args[1]],
+                "mv" => reg(1),
+                "seqz" => (reg(1) == 0) as i32,
+                "snez" => (reg(1) != 0) as i32,
+                "addi" => reg(1).wrapping_add(imm(2)),
+                "xori" => reg(1) ^ imm(2),
+                "slli" => reg(1) << imm(2),
+                "add" => reg(1).wrapping_add(reg(2)),
+                "sub" => reg(1).wrapping_sub(reg(2)),
+                "mul" => reg(1).wrapping_mul(reg(2)),
+                // division by zero and overflow are defined by RISC-V
+                "div" => match reg(2) {
+                    0 => -1,
+                    rhs => reg(1).wrapping_div(rhs),
+                },
+                "rem" => match reg(2) {
+                    0 => reg(1),
+                    rhs => reg(1).wrapping_rem(rhs),
+                },
+                "and" => reg(1) & reg(2),
+                "or" => reg(1) | reg(2),
+                "xor" => reg(1) ^ reg(2),
+                "sll" => reg(1) << (reg(2) & 31),
+                "srl" => ((reg(1) as u32) >> (reg(2) & 31)) as i32,
+                "sra" => reg(1) >> (reg(2) & 31),
+                "slt" => (reg(1) < reg(2)) as i32,
+                "sgt" => (reg(1) > reg(2)) as i32,
+                "lw" => self.load(self.addr(args[1])),
+                "sw" => {
+                    self.store(self.addr(args[1]), reg(0));
+                    continue;
+                }
+                "j" => {
+                    pc = self.labels[args[0]];
+                    continue;
+                }
+                "beqz" | "bnez" => {
+                    if (reg(0) == 0) == (op == "beqz") {
+                        pc = self.labels[args[1]];
+                    }
+                    continue;
+                }
+                "call" => {
+                    if !self.call_runtime(args[0]) {
+                        self.set_reg("ra", pc as i32);
+                        pc = self.labels[args[0]];
+                    }
+                    continue;
+                }
+                "ret" => match self.reg("ra") {
+                    -1 => return self.reg("a0"),
+                    ra => {
+                        pc = ra as usize;
+                        continue;
+                    }
+                },
+                _ => panic!("unknown instruction `{op}`"),
+            };
+            self.set_reg(args[0], value);
+        }
+        panic!("step limit exceeded");
+    }
+
+    /// Executes the given F extension instruction,
+    /// returns `false` if it is not one.
+    fn float_inst(&mut self, op: &str, args: &[&str]) -> bool {
+        let freg = |i: usize| self.freg(args[i]);
+        let value = match op {
+            "flw" => f32::from_bits(self.load(self.addr(args[1])) as u32),
+            "fsw" => {
+                self.store(self.addr(args[1]), freg(0).to_bits() as i32);
+                return true;
+            }
+            "fmv.w.x" => f32::from_bits(self.reg(args[1]) as u32),
+            "fmv.s" => freg(1),
+            "fadd.s" => freg(1) + freg(2),
+            "fsub.s" => freg(1) - freg(2),
+            "fmul.s" => freg(1) * freg(2),
+            "fdiv.s" => freg(1) / freg(2),
+            "fneg.s" => -freg(1),
+            "fcvt.s.w" => self.reg(args[1]) as f32,
+            _ => {
+                let value = match op {
+                    "fmv.x.w" => freg(1).to_bits() as i32,
+                    "feq.s" => (freg(1) == freg(2)) as i32,
+                    "flt.s" => (freg(1) < freg(2)) as i32,
+                    "fle.s" => (freg(1) <= freg(2)) as i32,
+                    // NaN is converted to the largest integer
+                    "fcvt.w.s" if freg(1).is_nan() => i32::MAX,
+                    "fcvt.w.s" => freg(1) as i32,
+                    _ => return false,
+                };
+                self.set_reg(args[0], value);
+                return true;
+            }
+        };
+        self.set_freg(args[0], value);
+        true
+    }
+}
+
+/// Formats the given `float` like `printf("%a", x)` in C.
+fn hex_float(x: f32) -> String {
+    let x = x as f64;
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if x.is_nan() {
+        return "nan".into();
+    } else if x.is_infinite() {
+        return format!("{sign}inf");
+    } else if x == 0.0 {
+        return format!("{sign}0x0p+0");
+    }
+    // `float`s are normal when converted to `double`
+    let bits = x.to_bits();
+    let exp = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let frac = format!("{:013x}", bits & ((1 << 52) - 1));
+    let frac = frac.trim_end_matches('0');
+    let dot = if frac.is_empty() { "" } else { "." };
+    format!("{sign}0x1{dot}{frac}p{exp:+}")
+}
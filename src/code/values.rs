@@ -1,34 +1,61 @@
-use std::fs::File;
 use std::io::Result;
 
+use crate::code::abi::ArgLoc;
 use crate::code::func::Slot;
 use crate::code::builder::AsmBuilder;
 
+/// Registers for passing arguments.
+pub const ARG_REGS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+
+/// Floating-point registers for passing `float` arguments.
+pub const FARG_REGS: [&str; 8] = ["fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7"];
+
+/// Allocatable registers that are not preserved across calls,
+/// `t0`-`t2` are reserved as temporary registers.
+pub const CALLER_SAVED_REGS: [&str; 12] = [
+    "t3", "t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+];
+
+/// Allocatable registers that are preserved across calls.
+pub const CALLEE_SAVED_REGS: [&str; 12] = [
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+];
+
 /// A global/local value.
+#[derive(Clone, Copy, PartialEq)]
 pub enum AsmValue<'i> {
     Global(&'i str),
     Local(Slot),
+    Reg(&'static str),
+    /// A floating-point register, only used for passing `float`s.
+    FReg(&'static str),
     Const(i32),
-    Arg(usize),
     Void,
 }
 
 /// Returns the assembly value of the given value data.
 macro_rules! asm_value {
-    ($info:expr, $v:expr) => {
-        AsmValue::from(cur_func!($info).slot_offset($v))
-    };
+    ($info:expr, $v:expr) => {{
+        let func = cur_func!($info);
+        match func.reg($v) {
+            Some(reg) => AsmValue::Reg(reg),
+            None => AsmValue::from(func.slot_offset($v)),
+        }
+    }};
 }
 pub(crate) use asm_value;
 
 impl<'i> AsmValue<'i> {
-     /// Returns `true` if the value is a pointer.
+    /// Returns `true` if the value is a pointer,
+    /// rather than an allocation.
+    ///
+    /// Allocations never live in registers.
     pub fn is_ptr(&self) -> bool {
-        matches!(self, Self::Local(slot) if slot.is_ptr)
+        matches!(self, Self::Local(slot) if slot.is_ptr) || matches!(self, Self::Reg(_))
     }
 
     /// Writes the assembly value to the given register.
-    pub fn write_to(&self, f: &mut File, reg: &'static str) -> Result<()> {
+    pub fn write_to(&self, f: &mut Vec<u8>, reg: &'static str) -> Result<()> {
         let mut builder = AsmBuilder::new(f, reg);
         match self {
             Self::Global(symbol) => {
@@ -36,28 +63,65 @@ impl<'i> AsmValue<'i> {
                 builder.lw(reg, reg, 0)
             }
             Self::Local(slot) => builder.lw(reg, "sp", slot.offset as i32),
+            Self::Reg(src) => builder.mv(reg, src),
+            Self::FReg(src) => builder.op1("fmv.x.w", reg, src),
             Self::Const(num) => builder.li(reg, *num),
-            _ => unreachable!(),
+            Self::Void => unreachable!(),
         }
     }
 
-    /// Writes the assembly value (argument) to the given register.
-    pub fn write_arg_to(&self, f: &mut File, reg: &'static str, sp_offset: usize) -> Result<()> {
-        let mut builder = AsmBuilder::new(f, reg);
+    /// Writes the assembly value to the given floating-point register,
+    /// `temp` is used if the value is a global or a constant.
+    pub fn write_to_freg(
+        &self,
+        f: &mut Vec<u8>,
+        freg: &'static str,
+        temp: &'static str,
+    ) -> Result<()> {
+        match self {
+            Self::Local(slot) => AsmBuilder::new(f, temp).flw(freg, "sp", slot.offset as i32),
+            Self::FReg(src) => AsmBuilder::new(f, temp).op1("fmv.s", freg, src),
+            _ => {
+                let reg = self.into_reg(f, temp)?;
+                AsmBuilder::new(f, temp).op1("fmv.w.x", freg, reg)
+            }
+        }
+    }
+
+    /// Returns a register holding the assembly value, writes the value to
+    /// the given temporary register if it is not in a register.
+    pub fn into_reg(self, f: &mut Vec<u8>, temp: &'static str) -> Result<&'static str> {
         match self {
-        Self::Arg(index) => {
-            if *index < 8 {
-                builder.mv(reg, &format!("a{}", *index))
-            } else {
-                builder.lw(reg, "sp", (sp_offset + (*index - 8) * 4) as i32)
+            Self::Reg(reg) => Ok(reg),
+            Self::Const(0) => Ok("x0"),
+            _ => {
+                self.write_to(f, temp)?;
+                Ok(temp)
             }
         }
-        _ => unreachable!(),
+    }
+
+    /// Returns the register that the result should be computed into,
+    /// which is the given temporary register if the value is not in a register.
+    pub fn dest_reg(&self, temp: &'static str) -> &'static str {
+        match self {
+            Self::Reg(reg) => reg,
+            _ => temp,
+        }
+    }
+
+    /// Writes the address of the assembly value to the given register.
+    pub fn write_addr_to(&self, f: &mut Vec<u8>, reg: &'static str) -> Result<()> {
+        let mut builder = AsmBuilder::new(f, reg);
+        match self {
+            Self::Global(symbol) => builder.la(reg, symbol),
+            Self::Local(slot) => builder.addi(reg, "sp", slot.offset as i32),
+            _ => unreachable!(),
         }
     }
 
     /// Reads the value of the given register to the assembly value.
-    pub fn read_from(&self, f: &mut File, reg: &'static str, temp: &'static str) -> Result<()> {
+    pub fn read_from(&self, f: &mut Vec<u8>, reg: &'static str, temp: &'static str) -> Result<()> {
         let mut builder = AsmBuilder::new(f, temp);
         match self {
             Self::Global(symbol) => {
@@ -65,17 +129,45 @@ impl<'i> AsmValue<'i> {
                 builder.sw(reg, temp, 0)
             }
             Self::Local(slot) => builder.sw(reg, "sp", slot.offset as i32),
+            Self::Reg(dest) => builder.mv(dest, reg),
+            Self::FReg(dest) => builder.op1("fmv.w.x", dest, reg),
             Self::Const(_) => unreachable!(),
-            Self::Arg(index) => {
-                if *index < 8 {
-                    builder.mv(&format!("a{}", *index), reg)
-                } else {
-                    builder.sw(reg, "sp", ((*index - 8) * 4) as i32)
-                }
-            }
             Self::Void => Ok(()),
         }
   }
+
+    /// Reads the value of the given floating-point register to the assembly value.
+    pub fn read_from_freg(
+        &self,
+        f: &mut Vec<u8>,
+        freg: &'static str,
+        temp: &'static str,
+    ) -> Result<()> {
+        let mut builder = AsmBuilder::new(f, temp);
+        match self {
+            Self::Global(symbol) => {
+                builder.la(temp, symbol)?;
+                builder.fsw(freg, temp, 0)
+            }
+            Self::Local(slot) => builder.fsw(freg, "sp", slot.offset as i32),
+            Self::Reg(dest) => builder.op1("fmv.x.w", dest, freg),
+            Self::FReg(dest) => builder.op1("fmv.s", dest, freg),
+            Self::Const(_) => unreachable!(),
+            Self::Void => Ok(()),
+        }
+    }
+}
+
+impl<'i> From<ArgLoc> for AsmValue<'i> {
+    /// Converts the location of an argument passed to the callee,
+    /// stack arguments are in the outgoing argument area at `sp`.
+    fn from(loc: ArgLoc) -> Self {
+        match loc {
+            ArgLoc::Reg(reg) => Self::Reg(reg),
+            ArgLoc::FReg(reg) => Self::FReg(reg),
+            ArgLoc::Stack(index) => Self::Local(Slot::new(index * 4, false)),
+        }
+    }
 }
 
 impl<'i> From<Option<Slot>> for AsmValue<'i> {
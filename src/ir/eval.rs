@@ -0,0 +1,179 @@
+use crate::ast::*;
+use crate::ir::scopes::{Scopes, Symbol};
+use crate::ir::values::Num;
+use crate::ir::{ErrorKind, Result};
+
+/// Trait for evaluating constant expressions.
+pub trait Evaluate {
+    /// Evaluates the expression, returns `Err` if the expression
+    /// is not a constant expression.
+    fn eval(&self, scopes: &Scopes) -> Result<Num>;
+}
+
+impl Evaluate for Exp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        self.lor.eval(scopes)
+    }
+}
+
+impl Evaluate for ConstExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        self.exp.eval(scopes)
+    }
+}
+
+impl Evaluate for LVal {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match scopes.value(&self.ident)? {
+            Symbol::Const(num) if self.indices.is_empty() => Ok(num),
+            Symbol::ConstArray(_, array) if self.indices.len() == array.dims.len() => {
+                let mut offset = 0;
+                for (index, &len) in self.indices.iter().zip(&array.dims) {
+                    match index.eval(scopes)? {
+                        Num::Int(index @ 0..) if (index as usize) < len => {
+                            offset = offset * len + index as usize;
+                        }
+                        Num::Float(_) => return Err(ErrorKind::NonIntIndex.at(index.span)),
+                        // out of bounds
+                        _ => return Err(ErrorKind::FailedToEval.into()),
+                    }
+                }
+                Ok(array.elems[offset])
+            }
+            _ => Err(ErrorKind::FailedToEval.into()),
+        }
+    }
+}
+
+impl Evaluate for PrimaryExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Exp(exp) => exp.eval(scopes),
+            Self::LVal(lval) => lval.eval(scopes),
+            Self::Number(num, _) => Ok(Num::Int(*num)),
+            Self::Float(num, _) => Ok(Num::Float(*num)),
+        }
+    }
+}
+
+impl Evaluate for UnaryExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Primary(exp) => exp.eval(scopes),
+            Self::Call(_) => Err(ErrorKind::FailedToEval.into()),
+            Self::Unary(op, exp, _) => exp.eval(scopes).map(|exp| match (op, exp) {
+                (UnaryOp::Pos, _) => exp,
+                (UnaryOp::Neg, Num::Int(num)) => Num::Int(num.wrapping_neg()),
+                (UnaryOp::Neg, Num::Float(num)) => Num::Float(-num),
+                (UnaryOp::Not, _) => Num::Int(exp.is_zero() as i32),
+            }),
+        }
+    }
+}
+
+impl Evaluate for MulExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Unary(exp) => exp.eval(scopes),
+            Self::MulUnary(lhs, op, rhs, _) => match (lhs.eval(scopes)?, rhs.eval(scopes)?) {
+                (Num::Int(lhs), Num::Int(rhs)) => match op {
+                    MulOp::Mul => Ok(Num::Int(lhs.wrapping_mul(rhs))),
+                    _ if rhs == 0 => Err(ErrorKind::FailedToEval.into()),
+                    MulOp::Div => Ok(Num::Int(lhs.wrapping_div(rhs))),
+                    MulOp::Mod => Ok(Num::Int(lhs.wrapping_rem(rhs))),
+                },
+                (lhs, rhs) => match op {
+                    MulOp::Mul => Ok(Num::Float(lhs.float() * rhs.float())),
+                    MulOp::Div => Ok(Num::Float(lhs.float() / rhs.float())),
+                    MulOp::Mod => Err(ErrorKind::FloatMod.into()),
+                },
+            },
+        }
+    }
+}
+
+impl Evaluate for AddExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Mul(exp) => exp.eval(scopes),
+            Self::AddMul(lhs, op, rhs, _) => Ok(match (lhs.eval(scopes)?, rhs.eval(scopes)?) {
+                (Num::Int(lhs), Num::Int(rhs)) => Num::Int(match op {
+                    AddOp::Add => lhs.wrapping_add(rhs),
+                    AddOp::Sub => lhs.wrapping_sub(rhs),
+                }),
+                (lhs, rhs) => Num::Float(match op {
+                    AddOp::Add => lhs.float() + rhs.float(),
+                    AddOp::Sub => lhs.float() - rhs.float(),
+                }),
+            }),
+        }
+    }
+}
+
+impl Evaluate for RelExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Add(exp) => exp.eval(scopes),
+            Self::RelAdd(lhs, op, rhs, _) => {
+                let result = match (lhs.eval(scopes)?, rhs.eval(scopes)?) {
+                    (Num::Int(lhs), Num::Int(rhs)) => compare(op, lhs, rhs),
+                    (lhs, rhs) => compare(op, lhs.float(), rhs.float()),
+                };
+                Ok(Num::Int(result as i32))
+            }
+        }
+    }
+}
+
+impl Evaluate for EqExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Rel(exp) => exp.eval(scopes),
+            Self::EqRel(lhs, op, rhs, _) => {
+                let (lhs, rhs) = (lhs.eval(scopes)?, rhs.eval(scopes)?);
+                let equal = match (lhs, rhs) {
+                    (Num::Int(lhs), Num::Int(rhs)) => lhs == rhs,
+                    (lhs, rhs) => lhs.float() == rhs.float(),
+                };
+                Ok(Num::Int(match op {
+                    EqOp::Eq => equal,
+                    EqOp::Neq => !equal,
+                } as i32))
+            }
+        }
+    }
+}
+
+impl Evaluate for LAndExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::Eq(exp) => exp.eval(scopes),
+            Self::LAndEq(lhs, rhs, _) => {
+                let result = !lhs.eval(scopes)?.is_zero() && !rhs.eval(scopes)?.is_zero();
+                Ok(Num::Int(result as i32))
+            }
+        }
+    }
+}
+
+impl Evaluate for LOrExp {
+    fn eval(&self, scopes: &Scopes) -> Result<Num> {
+        match self {
+            Self::LAnd(exp) => exp.eval(scopes),
+            Self::LOrLAnd(lhs, rhs, _) => {
+                let result = !lhs.eval(scopes)?.is_zero() || !rhs.eval(scopes)?.is_zero();
+                Ok(Num::Int(result as i32))
+            }
+        }
+    }
+}
+
+/// Compares two numbers of the same type by the given relational operator.
+fn compare<T: PartialOrd>(op: &RelOp, lhs: T, rhs: T) -> bool {
+    match op {
+        RelOp::Lt => lhs < rhs,
+        RelOp::Gt => lhs > rhs,
+        RelOp::Le => lhs <= rhs,
+        RelOp::Ge => lhs >= rhs,
+    }
+}
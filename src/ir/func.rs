@@ -1,19 +1,28 @@
-use koopa::ir::Value;
+use std::collections::HashSet;
+
+use koopa::ir::{Value, ValueKind};
 use koopa::ir::{builder::LocalBuilder, builder_traits::*};
 use koopa::ir::{BasicBlock, Function, Program, Type};
 
+use crate::ast::BType;
+
 /// Function information.
 pub struct FunctionInfo {
   func: Function,
   entry: BasicBlock,
   end: BasicBlock,
   cur: BasicBlock,
-  ret_val: Option<Value>,
+  ret_val: Option<(Value, BType)>,
 }
 
 impl FunctionInfo {
     /// Creates a new function information.
-    pub fn new(func: Function, entry: BasicBlock, end: BasicBlock, ret_val: Option<Value>) -> Self {
+    pub fn new(
+        func: Function,
+        entry: BasicBlock,
+        end: BasicBlock,
+        ret_val: Option<(Value, BType)>,
+    ) -> Self {
         Self {
             func,
             entry,
@@ -24,7 +33,7 @@ impl FunctionInfo {
     }
 
     /// Returns the curren function.
-    pub fn _func(&self) -> Function {
+    pub fn func(&self) -> Function {
         self.func
     }
 
@@ -33,8 +42,8 @@ impl FunctionInfo {
         self.end
     }
 
-    /// Returns the return value.
-    pub fn ret_val(&self) -> Option<Value> {
+    /// Returns the allocation of the return value and its type.
+    pub fn ret_val(&self) -> Option<(Value, BType)> {
         self.ret_val
     }
 
@@ -47,6 +56,20 @@ impl FunctionInfo {
         .basic_block(name.map(|s| s.into()))
     }
 
+    /// Creates a new basic block with parameters of the given types in function.
+    pub fn new_bb_with_params(
+        &self,
+        program: &mut Program,
+        name: Option<&str>,
+        params_ty: Vec<Type>,
+    ) -> BasicBlock {
+        program
+        .func_mut(self.func)
+        .dfg_mut()
+        .new_bb()
+        .basic_block_with_params(name.map(|s| s.into()), params_ty)
+    }
+
     /// Creates a new value in function.
     pub fn new_value<'p>(&self, program: &'p mut Program) -> LocalBuilder<'p> {
         program.func_mut(self.func).dfg_mut().new_value()
@@ -81,7 +104,7 @@ impl FunctionInfo {
     }
 
     /// Creates a new allocation and inserts to the entry block.
-    pub fn _new_alloc(&self, program: &mut Program, ty: Type, name: Option<&str>) -> Value {
+    pub fn new_alloc(&self, program: &mut Program, ty: Type, name: Option<&str>) -> Value {
         let alloc = self.new_value(program).alloc(ty);
         if let Some(name) = name {
         program
@@ -93,6 +116,34 @@ impl FunctionInfo {
         alloc
     }
 
+    /// Returns `true` if the current basic block is reachable
+    /// from the entry basic block.
+    pub fn is_cur_reachable(&self, program: &Program) -> bool {
+        let func = program.func(self.func);
+        let mut visited = HashSet::new();
+        let mut worklist = vec![self.entry];
+        while let Some(bb) = worklist.pop() {
+            if bb == self.cur {
+                return true;
+            }
+            if !visited.insert(bb) {
+                continue;
+            }
+            let last = func.layout().bbs().node(&bb).and_then(|n| n.insts().back_key());
+            if let Some(&last) = last {
+                match func.dfg().value(last).kind() {
+                    ValueKind::Jump(jump) => worklist.push(jump.target()),
+                    ValueKind::Branch(br) => {
+                        worklist.push(br.true_bb());
+                        worklist.push(br.false_bb());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
     /// Seals the entry block.
     pub fn seal_entry(&self, program: &mut Program, next: BasicBlock) {
         let jump = self.new_value(program).jump(next);
@@ -109,7 +160,7 @@ impl FunctionInfo {
         self.push_bb(program, self.end);
 
         // generate return
-        let value = self.ret_val.map(|alloc| {
+        let value = self.ret_val.map(|(alloc, _)| {
             let value = self.new_value(program).load(alloc);
             self.push_inst(program, value);
             value
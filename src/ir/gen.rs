@@ -1,36 +1,75 @@
+use std::rc::Rc;
 
 use koopa::ir::builder_traits::*;
-use koopa::ir::{FunctionData, Program, Type};
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Program, Type, TypeKind, Value};
 
 use crate::ast::*;
-use crate::ir::Result;
+use crate::ir::{ErrorKind, Result, WarningKind};
+use crate::ir::eval::Evaluate;
 use crate::ir::func::FunctionInfo;
-use crate::ir::scopes::{cur_func, cur_func_mut, Scopes};
+use crate::ir::scopes::{cur_func, cur_func_mut, ConstElems, FuncSig, Scopes, Symbol};
+use crate::ir::values::{ExpValue, Initializer, Num};
 
 /// Trait for generating Koopa IR program.
 pub trait GenerateProgram<'ast> {
     type Out;
 
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out>;
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out>;
 }
 
 impl<'ast> GenerateProgram<'ast> for CompUnit {
     type Out = ();
   
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out> {
-        self.func_def.generate(program, scopes)?;
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // report errors and keep going, so that all errors can be reported
+        for item in &self.items {
+            if let Err(err) = item.generate(program, scopes) {
+                scopes.diags.error(err);
+            }
+        }
         Ok(())
     }
 }
 
+impl<'ast> GenerateProgram<'ast> for GlobalItem {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Decl(decl) => decl.generate(program, scopes),
+            Self::FuncDef(def) => def.generate(program, scopes).map_err(|e| e.or_span(def.span)),
+            Self::Error => Ok(()),
+        }
+    }
+}
+
 impl<'ast> GenerateProgram<'ast> for FuncDef {
     type Out = ();
   
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out> {
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
         // create new fucntion
-        let params_ty = Vec::new();
+        let params = self
+            .params
+            .iter()
+            .map(|p| {
+                let ty = p.generate(program, scopes).unwrap_or_else(|err| {
+                    scopes.diags.error(err.or_span(p.span));
+                    Type::get_i32()
+                });
+                (Some(format!("@{}", p.ident)), ty)
+            })
+            .collect();
         let ret_ty = self.func_type.generate(program, scopes)?;
-        let mut data = FunctionData::new(format!("@{}", self.ident), params_ty, ret_ty);
+        let mut data = FunctionData::with_param_names(format!("@{}", self.ident), params, ret_ty);
+        let params = data.params().to_owned();
+        let sig = FuncSig {
+            params: self.params.iter().map(|p| p.ty).collect(),
+            ret: match self.func_type {
+                FuncType::Int => Some(BType::Int),
+                FuncType::Float => Some(BType::Float),
+                FuncType::Void => None,
+            },
+        };
 
         // generate entry/end/cur block
         let entry = data.dfg_mut().new_bb().basic_block(Some("%entry".into()));
@@ -38,42 +77,163 @@ impl<'ast> GenerateProgram<'ast> for FuncDef {
         let cur = data.dfg_mut().new_bb().basic_block(None);
 
         // generate return value
-        let mut ret_val = None;
-        if matches!(self.func_type, FuncType::Int) {
+        let ret_val = sig.ret.map(|ty| {
             let alloc = data.dfg_mut().new_value().alloc(Type::get_i32());
             data.dfg_mut().set_value_name(alloc, Some("%ret".into()));
-            ret_val = Some(alloc);
-        }
+            (alloc, ty)
+        });
 
         // update function information
         let func = program.new_func(data);
         let mut info = FunctionInfo::new(func, entry, end, ret_val);
         info.push_bb(program, entry);
-        if let Some(ret_val) = info.ret_val() {
+        if let Some((ret_val, _)) = info.ret_val() {
             info.push_inst(program, ret_val);
         }
         info.push_bb(program, cur);
 
-        // update scope
+        // update scope, the body is still checked if the function is redefined
+        if let Err(err) = scopes.new_func(&self.ident, func, sig) {
+            scopes.diags.error(err.or_span(self.span));
+        }
+        scopes.enter();
+
+        // generate allocations for parameters
+        for (param, value) in self.params.iter().zip(params) {
+            let ty = program.func(func).dfg().value(value).ty().clone();
+            let alloc = info.new_alloc(program, ty, Some(&param.ident));
+            let store = info.new_value(program).store(value, alloc);
+            info.push_inst(program, store);
+            check_shadowing(scopes, &param.ident, param.span);
+            if let Err(err) = scopes.new_value(&param.ident, Symbol::Var(alloc, param.ty)) {
+                scopes.diags.error(err.or_span(param.span));
+            }
+        }
         scopes.cur_func = Some(info);
 
-        // generate function body
-        self.block.generate(program, scopes)?;
+        // generate function body, which shares the scope with parameters
+        generate_items(program, scopes, &self.block.items)?;
+        scopes.exit();
 
         // handle end basic block
         let mut info = scopes.cur_func.take().unwrap();
         info.seal_entry(program, cur);
+        if let Some((ret_val, _)) = info.ret_val() {
+            // falling off the end of a non-void function returns 0
+            if info.is_cur_reachable(program) {
+                if self.ident != "main" {
+                    let end = Span::new(self.span.end - 1, self.span.end);
+                    let warning = WarningKind::ImplicitReturn(self.ident.clone()).at(end);
+                    scopes.diags.warn(warning);
+                }
+                let zero = info.new_value(program).integer(0);
+                let store = info.new_value(program).store(zero, ret_val);
+                info.push_inst(program, store);
+            }
+        }
         info.seal_func(program);
         Ok(())
     }
 }  
 
+impl<'ast> GenerateProgram<'ast> for FuncFParam {
+    type Out = Type;
+
+    fn generate(&'ast self, _: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match &self.dims {
+            // array parameters decay to pointers
+            Some(dims) => {
+                let dims = eval_dims(scopes, dims)?;
+                Type::get_pointer(array_type(&dims))
+            }
+            None => Type::get_i32(),
+        })
+    }
+}
+
 impl<'ast> GenerateProgram<'ast> for FuncType {
     type Out = Type;
   
-    fn generate(&'ast self, _: &mut Program, _scopes: &mut Scopes) -> Result<Self::Out> {
+    fn generate(&'ast self, _: &mut Program, _scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            // `float` values are represented by their bit patterns
+            Self::Int | Self::Float => Type::get_i32(),
+            Self::Void => Type::get_unit(),
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for Decl {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Const(c) => c.generate(program, scopes),
+            Self::Var(v) => v.generate(program, scopes),
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for ConstDecl {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        for def in &self.defs {
+            if let Err(err) = generate_const_def(program, scopes, self.ty, def) {
+                scopes.diags.error(err.or_span(def.span));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for ConstInitVal {
+    type Out = Initializer;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
         Ok(match self {
-            Self::Int => Type::get_i32(),
+            Self::Exp(exp) => Initializer::Const(exp.generate(program, scopes)?),
+            Self::List(list) => Initializer::List(
+                list.iter()
+                    .map(|v| v.generate(program, scopes))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for VarDecl {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        for def in &self.defs {
+            if let Err(err) = generate_var_def(program, scopes, self.ty, def) {
+                scopes.diags.error(err.or_span(def.span));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for InitVal {
+    type Out = Initializer;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            // initializers of global variables must be constant
+            Self::Exp(exp) if scopes.is_global() => {
+                let num = exp.eval(scopes).map_err(|e| e.or_span(exp.span))?;
+                Initializer::Const(num)
+            }
+            Self::Exp(exp) => {
+                let (value, ty) = generate_operand(program, scopes, exp)?;
+                Initializer::Value(value, ty)
+            }
+            Self::List(list) => Initializer::List(
+                list.iter()
+                    .map(|v| v.generate(program, scopes))
+                    .collect::<Result<_>>()?,
+            ),
         })
     }
 }
@@ -81,42 +241,1033 @@ impl<'ast> GenerateProgram<'ast> for FuncType {
 impl<'ast> GenerateProgram<'ast> for Block {
     type Out = ();
   
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out> {
-        self.stmt.generate(program, scopes)?;
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        scopes.enter();
+        generate_items(program, scopes, &self.items)?;
+        scopes.exit();
         Ok(())
     }
 }
 
+impl<'ast> GenerateProgram<'ast> for BlockItem {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Decl(decl) => decl.generate(program, scopes),
+            Self::Stmt(stmt) => stmt.generate(program, scopes),
+        }
+    }
+}
+
 impl<'ast> GenerateProgram<'ast> for Stmt {
     type Out = ();
   
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out> {
-        match self {
-            Self::Return(s) => s.generate(program, scopes),
-          }
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        let result = match self {
+            Self::Assign(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::ExpStmt(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::Block(s) => s.generate(program, scopes),
+            Self::If(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::While(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::Break(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::Continue(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+            Self::Return(s) => s.generate(program, scopes).map_err(|e| e.or_span(s.span)),
+        };
+        // report errors and continue with the next statement
+        if let Err(err) = result {
+            scopes.diags.error(err);
+        }
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for Assign {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // only variables can be assigned
+        let lval = &self.lval;
+        if !matches!(scopes.value(&lval.ident).map_err(|e| e.or_span(lval.span))?, Symbol::Var(..)) {
+            return Err(ErrorKind::AssignToConst(lval.ident.clone()).at(lval.span));
+        }
+        let (value, ty) = generate_operand(program, scopes, &self.exp)?;
+        let dest = self.lval.generate(program, scopes).map_err(|e| e.or_span(self.lval.span))?;
+        let (dest, dest_ty) = dest.into_ptr()?;
+        let value = generate_conversion(program, scopes, value, ty, dest_ty);
+        let info = cur_func!(scopes);
+        let store = info.new_value(program).store(value, dest);
+        info.push_inst(program, store);
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for ExpStmt {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        if let Some(exp) = &self.exp {
+            let value = exp.generate(program, scopes)?;
+            if let (ExpValue::Int(_) | ExpValue::Float(_), Some(call)) = (value, as_call(exp)) {
+                let warning = WarningKind::UnusedResult(call.ident.clone()).at(call.span);
+                scopes.diags.warn(warning);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for If {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // generate condition
+        let cond = generate_cond(program, scopes, &self.cond)?;
+
+        // generate branch
+        let info = cur_func!(scopes);
+        let then_bb = info.new_bb(program, Some("%if_then"));
+        let else_bb = self
+            .else_then
+            .as_ref()
+            .map(|_| info.new_bb(program, Some("%if_else")));
+        let end_bb = info.new_bb(program, Some("%if_end"));
+        let br = info.new_value(program).branch(cond, then_bb, else_bb.unwrap_or(end_bb));
+        info.push_inst(program, br);
+
+        // generate then statement
+        cur_func_mut!(scopes).push_bb(program, then_bb);
+        self.then.generate(program, scopes)?;
+        let info = cur_func!(scopes);
+        let jump = info.new_value(program).jump(end_bb);
+        info.push_inst(program, jump);
+
+        // generate else statement
+        if let (Some(else_then), Some(else_bb)) = (&self.else_then, else_bb) {
+            cur_func_mut!(scopes).push_bb(program, else_bb);
+            else_then.generate(program, scopes)?;
+            let info = cur_func!(scopes);
+            let jump = info.new_value(program).jump(end_bb);
+            info.push_inst(program, jump);
+        }
+
+        // push the end basic block
+        cur_func_mut!(scopes).push_bb(program, end_bb);
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for While {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // jump to the entry basic block
+        let info = cur_func_mut!(scopes);
+        let entry_bb = info.new_bb(program, Some("%while_entry"));
+        let body_bb = info.new_bb(program, Some("%while_body"));
+        let end_bb = info.new_bb(program, Some("%while_end"));
+        let jump = info.new_value(program).jump(entry_bb);
+        info.push_inst(program, jump);
+
+        // generate condition
+        info.push_bb(program, entry_bb);
+        let cond = generate_cond(program, scopes, &self.cond)?;
+        let info = cur_func!(scopes);
+        let br = info.new_value(program).branch(cond, body_bb, end_bb);
+        info.push_inst(program, br);
+
+        // generate loop body
+        cur_func_mut!(scopes).push_bb(program, body_bb);
+        scopes.enter_loop(entry_bb, end_bb);
+        self.body.generate(program, scopes)?;
+        scopes.exit_loop();
+        let info = cur_func_mut!(scopes);
+        let jump = info.new_value(program).jump(entry_bb);
+        info.push_inst(program, jump);
+
+        // push the end basic block
+        info.push_bb(program, end_bb);
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for Break {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        let exit = scopes.loop_info().ok_or(ErrorKind::NotInLoop("break"))?.exit;
+        generate_jump(program, scopes, exit);
+        Ok(())
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for Continue {
+    type Out = ();
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        let entry = scopes.loop_info().ok_or(ErrorKind::NotInLoop("continue"))?.entry;
+        generate_jump(program, scopes, entry);
+        Ok(())
     }
 }
 
 impl<'ast> GenerateProgram<'ast> for Return {
     type Out = ();
   
-    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes) -> Result<Self::Out> {
-        if let Some(ret_val) = cur_func!(scopes).ret_val() {
-            // generate store
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // check if the return value matches the return type
+        let info = cur_func!(scopes);
+        let name = &program.func(info.func()).name()[1..];
+        let result = match (info.ret_val(), &self.exp) {
+            (Some((ret_val, ty)), Some(exp)) => {
+                generate_converted(program, scopes, exp, ty).map(|value| {
+                    let info = cur_func!(scopes);
+                    let store = info.new_value(program).store(value, ret_val);
+                    info.push_inst(program, store);
+                })
+            }
+            (None, None) => Ok(()),
+            (Some(_), None) => Err(ErrorKind::NoRetValInIntFunc(name.into()).into()),
+            (None, Some(exp)) => Err(ErrorKind::RetValInVoidFunc(name.into()).at(exp.span)),
+        };
+
+        // jump to the end basic block, even if the return value is invalid,
+        // so that the end of the function is not reported as reachable
+        let end = cur_func!(scopes).end();
+        generate_jump(program, scopes, end);
+        result
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for Exp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        self.lor.generate(program, scopes).map_err(|e| e.or_span(self.span))
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for ConstExp {
+    type Out = Num;
+
+    fn generate(&'ast self, _: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        self.eval(scopes).map_err(|e| e.or_span(self.exp.span))
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for LVal {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        scopes.mark_used(&self.ident);
+        let (mut ptr, ty) = match scopes.value(&self.ident)? {
+            Symbol::Var(alloc, ty) => (alloc, ty),
+            Symbol::ConstArray(alloc, array) => (alloc, array.ty),
+            Symbol::Const(num) if self.indices.is_empty() => {
+                let value = cur_func!(scopes).new_value(program).integer(num.bits());
+                return Ok(ExpValue::num(value, num.ty()));
+            }
+            Symbol::Const(_) => return Err(ErrorKind::DimMismatch(self.ident.clone()).into()),
+        };
+
+        // array parameters are pointers stored in allocations, load them first
+        let is_ptr_param = is_ptr_ptr(&scopes.ty(program, ptr));
+        if is_ptr_param {
+            let info = cur_func!(scopes);
+            ptr = info.new_value(program).load(ptr);
+            info.push_inst(program, ptr);
+        }
+
+        // generate element pointer
+        for (i, index) in self.indices.iter().enumerate() {
+            let is_ptr = is_ptr_param && i == 0;
+            if !is_ptr && !is_array_ptr(&scopes.ty(program, ptr)) {
+                return Err(ErrorKind::DimMismatch(self.ident.clone()).into());
+            }
+            let index = generate_index(program, scopes, index)?;
             let info = cur_func!(scopes);
-            let value = cur_func!(scopes).new_value(program).integer(self.num);
-            let store = info.new_value(program).store(value, ret_val);
+            ptr = if is_ptr {
+                info.new_value(program).get_ptr(ptr, index)
+            } else {
+                info.new_value(program).get_elem_ptr(ptr, index)
+            };
+            info.push_inst(program, ptr);
+        }
+
+        if is_ptr_param && self.indices.is_empty() {
+            // the array parameter itself
+            Ok(ExpValue::ArrPtr(ptr, ty))
+        } else if is_array_ptr(&scopes.ty(program, ptr)) {
+            // partially indexed array, decays to pointer to its first element
+            let info = cur_func!(scopes);
+            let zero = info.new_value(program).integer(0);
+            let ptr = info.new_value(program).get_elem_ptr(ptr, zero);
+            info.push_inst(program, ptr);
+            Ok(ExpValue::ArrPtr(ptr, ty))
+        } else {
+            Ok(ExpValue::Ptr(ptr, ty))
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for PrimaryExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Exp(exp) => exp.generate(program, scopes),
+            Self::LVal(lval) => {
+                match lval.generate(program, scopes).map_err(|e| e.or_span(lval.span))? {
+                    ExpValue::Ptr(ptr, ty) => {
+                        let info = cur_func!(scopes);
+                        let load = info.new_value(program).load(ptr);
+                        info.push_inst(program, load);
+                        Ok(ExpValue::num(load, ty))
+                    }
+                    value => Ok(value),
+                }
+            }
+            Self::Number(num, _) => Ok(ExpValue::Int(cur_func!(scopes).new_value(program).integer(*num))),
+            Self::Float(num, _) => {
+                let value = cur_func!(scopes).new_value(program).integer(num.to_bits() as i32);
+                Ok(ExpValue::Float(value))
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for UnaryExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Primary(exp) => exp.generate(program, scopes),
+            Self::Call(call) => call.generate(program, scopes).map_err(|e| e.or_span(call.span)),
+            Self::Unary(op, exp, _) => {
+                let (exp, ty) = generate_operand(program, scopes, &**exp)?;
+                Ok(match (op, ty) {
+                    (UnaryOp::Pos, _) => ExpValue::num(exp, ty),
+                    (UnaryOp::Neg, BType::Int) => {
+                        let zero = cur_func!(scopes).new_value(program).integer(0);
+                        ExpValue::Int(generate_binary(program, scopes, BinaryOp::Sub, zero, exp))
+                    }
+                    (UnaryOp::Neg, BType::Float) => {
+                        ExpValue::Float(generate_float_call(program, scopes, "__negsf2", vec![exp]))
+                    }
+                    (UnaryOp::Not, BType::Int) => {
+                        let zero = cur_func!(scopes).new_value(program).integer(0);
+                        ExpValue::Int(generate_binary(program, scopes, BinaryOp::Eq, exp, zero))
+                    }
+                    (UnaryOp::Not, BType::Float) => {
+                        // the bit pattern of `0.0` is also 0
+                        let zero = cur_func!(scopes).new_value(program).integer(0);
+                        ExpValue::Int(generate_float_cmp(program, scopes, BinaryOp::Eq, exp, zero))
+                    }
+                })
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for FuncCall {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        // variables shadow functions with the same name
+        if scopes.value(&self.ident).is_ok() {
+            scopes.mark_used(&self.ident);
+            return Err(ErrorKind::CallNonFunc(self.ident.clone()).into());
+        }
+        let (callee, sig) = scopes.func(&self.ident)?;
+        let params_ty = match program.func(callee).ty().kind() {
+            TypeKind::Function(params, _) => params.clone(),
+            _ => unreachable!(),
+        };
+
+        // generate arguments, which must match the parameters
+        if params_ty.len() != self.args.len() {
+            let (params, args) = (params_ty.len(), self.args.len());
+            return Err(ErrorKind::ArgCountMismatch(self.ident.clone(), params, args).into());
+        }
+        let mut args = Vec::new();
+        for ((arg, ty), &param_ty) in self.args.iter().zip(params_ty).zip(&sig.params) {
+            let mismatch = || ErrorKind::ArgTypeMismatch(self.ident.clone()).at(arg.span);
+            let value = match arg.generate(program, scopes)? {
+                ExpValue::ArrPtr(ptr, elem_ty) => {
+                    if elem_ty != param_ty || scopes.ty(program, ptr) != ty {
+                        return Err(mismatch());
+                    }
+                    ptr
+                }
+                // numbers are converted to the types of parameters
+                value => {
+                    let (value, from) = value.into_num().map_err(|e| e.or_span(arg.span))?;
+                    if !ty.is_i32() {
+                        return Err(mismatch());
+                    }
+                    generate_conversion(program, scopes, value, from, param_ty)
+                }
+            };
+            args.push(value);
+        }
+
+        // generate call
+        let info = cur_func!(scopes);
+        let call = info.new_value(program).call(callee, args);
+        info.push_inst(program, call);
+        Ok(match sig.ret {
+            Some(ty) => ExpValue::num(call, ty),
+            None => ExpValue::Void,
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for MulExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Unary(exp) => exp.generate(program, scopes),
+            Self::MulUnary(lhs, op, rhs, span) => {
+                let lhs = generate_operand(program, scopes, &**lhs)?;
+                // only integer division by zero is undefined
+                let int_div = !matches!(op, MulOp::Mul) && lhs.1 == BType::Int;
+                if int_div && matches!(rhs.eval(scopes), Ok(Num::Int(0))) {
+                    scopes.diags.warn(WarningKind::DivByZero.at(*span));
+                }
+                let rhs = generate_operand(program, scopes, rhs)?;
+                let op = op.generate(program, scopes)?;
+                generate_num_binary(program, scopes, op, lhs, rhs).map_err(|e| e.or_span(*span))
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for AddExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Mul(exp) => exp.generate(program, scopes),
+            Self::AddMul(lhs, op, rhs, _) => {
+                let lhs = generate_operand(program, scopes, &**lhs)?;
+                let rhs = generate_operand(program, scopes, rhs)?;
+                let op = op.generate(program, scopes)?;
+                generate_num_binary(program, scopes, op, lhs, rhs)
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for RelExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Add(exp) => exp.generate(program, scopes),
+            Self::RelAdd(lhs, op, rhs, _) => {
+                let lhs = generate_operand(program, scopes, &**lhs)?;
+                let rhs = generate_operand(program, scopes, rhs)?;
+                let op = op.generate(program, scopes)?;
+                generate_num_binary(program, scopes, op, lhs, rhs)
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for EqExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Rel(exp) => exp.generate(program, scopes),
+            Self::EqRel(lhs, op, rhs, _) => {
+                let lhs = generate_operand(program, scopes, &**lhs)?;
+                let rhs = generate_operand(program, scopes, rhs)?;
+                let op = op.generate(program, scopes)?;
+                generate_num_binary(program, scopes, op, lhs, rhs)
+            }
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for LAndExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::Eq(exp) => exp.generate(program, scopes),
+            Self::LAndEq(lhs, rhs, _) => generate_logical(program, scopes, &**lhs, rhs, true),
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for LOrExp {
+    type Out = ExpValue;
+
+    fn generate(&'ast self, program: &mut Program, scopes: &mut Scopes<'ast>) -> Result<Self::Out> {
+        match self {
+            Self::LAnd(exp) => exp.generate(program, scopes),
+            Self::LOrLAnd(lhs, rhs, _) => generate_logical(program, scopes, &**lhs, rhs, false),
+        }
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for MulOp {
+    type Out = BinaryOp;
+
+    fn generate(&'ast self, _: &mut Program, _: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            Self::Mul => BinaryOp::Mul,
+            Self::Div => BinaryOp::Div,
+            Self::Mod => BinaryOp::Mod,
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for AddOp {
+    type Out = BinaryOp;
+
+    fn generate(&'ast self, _: &mut Program, _: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            Self::Add => BinaryOp::Add,
+            Self::Sub => BinaryOp::Sub,
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for RelOp {
+    type Out = BinaryOp;
+
+    fn generate(&'ast self, _: &mut Program, _: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            Self::Lt => BinaryOp::Lt,
+            Self::Gt => BinaryOp::Gt,
+            Self::Le => BinaryOp::Le,
+            Self::Ge => BinaryOp::Ge,
+        })
+    }
+}
+
+impl<'ast> GenerateProgram<'ast> for EqOp {
+    type Out = BinaryOp;
+
+    fn generate(&'ast self, _: &mut Program, _: &mut Scopes<'ast>) -> Result<Self::Out> {
+        Ok(match self {
+            Self::Eq => BinaryOp::Eq,
+            Self::Neq => BinaryOp::NotEq,
+        })
+    }
+}
+
+/// Generates the given expression as a number operand and its type,
+/// errors without spans are reported at the operand.
+fn generate_operand<'ast, E>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    exp: &'ast E,
+) -> Result<(Value, BType)>
+where
+    E: GenerateProgram<'ast, Out = ExpValue> + Spanned,
+{
+    exp.generate(program, scopes)
+        .and_then(ExpValue::into_num)
+        .map_err(|e| e.or_span(exp.span()))
+}
+
+/// Generates the given expression as an operand of the given type.
+fn generate_converted<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    exp: &'ast Exp,
+    ty: BType,
+) -> Result<Value> {
+    let (value, from) = generate_operand(program, scopes, exp)?;
+    Ok(generate_conversion(program, scopes, value, from, ty))
+}
+
+/// Generates the given expression as a condition,
+/// which is non-zero if the expression is true.
+fn generate_cond<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    exp: &'ast Exp,
+) -> Result<Value> {
+    match generate_operand(program, scopes, exp)? {
+        (value, BType::Int) => Ok(value),
+        (value, ty) => Ok(generate_bool(program, scopes, value, ty)),
+    }
+}
+
+/// Generates the given expression as an array subscript,
+/// which must be an integer.
+fn generate_index<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    exp: &'ast Exp,
+) -> Result<Value> {
+    match generate_operand(program, scopes, exp)? {
+        (value, BType::Int) => Ok(value),
+        (_, BType::Float) => Err(ErrorKind::NonIntIndex.at(exp.span)),
+    }
+}
+
+/// Converts the given number from type `from` to type `to`,
+/// `float` is truncated toward zero when converted to `int`.
+fn generate_conversion(
+    program: &mut Program,
+    scopes: &Scopes,
+    value: Value,
+    from: BType,
+    to: BType,
+) -> Value {
+    let routine = match (from, to) {
+        (BType::Int, BType::Float) => "__floatsisf",
+        (BType::Float, BType::Int) => "__fixsfsi",
+        _ => return value,
+    };
+    generate_float_call(program, scopes, routine, vec![value])
+}
+
+/// Generates a binary operation on numbers, both operands are converted
+/// to `float` if either of them is a `float`.
+fn generate_num_binary(
+    program: &mut Program,
+    scopes: &Scopes,
+    op: BinaryOp,
+    (lhs, lhs_ty): (Value, BType),
+    (rhs, rhs_ty): (Value, BType),
+) -> Result<ExpValue> {
+    if lhs_ty == BType::Int && rhs_ty == BType::Int {
+        return Ok(ExpValue::Int(generate_binary(program, scopes, op, lhs, rhs)));
+    }
+    if matches!(op, BinaryOp::Mod) {
+        return Err(ErrorKind::FloatMod.into());
+    }
+    let lhs = generate_conversion(program, scopes, lhs, lhs_ty, BType::Float);
+    let rhs = generate_conversion(program, scopes, rhs, rhs_ty, BType::Float);
+    let routine = match op {
+        BinaryOp::Add => "__addsf3",
+        BinaryOp::Sub => "__subsf3",
+        BinaryOp::Mul => "__mulsf3",
+        BinaryOp::Div => "__divsf3",
+        // comparisons
+        _ => return Ok(ExpValue::Int(generate_float_cmp(program, scopes, op, lhs, rhs))),
+    };
+    Ok(ExpValue::Float(generate_float_call(program, scopes, routine, vec![lhs, rhs])))
+}
+
+/// Compares two `float`s by the given comparison operator.
+///
+/// The comparison routines return an integer that has the same relation
+/// to 0 as `lhs` to `rhs`, or makes the comparison false if either of
+/// them is NaN.
+fn generate_float_cmp(
+    program: &mut Program,
+    scopes: &Scopes,
+    op: BinaryOp,
+    lhs: Value,
+    rhs: Value,
+) -> Value {
+    let routine = match op {
+        BinaryOp::Eq => "__eqsf2",
+        BinaryOp::NotEq => "__nesf2",
+        BinaryOp::Lt => "__ltsf2",
+        BinaryOp::Gt => "__gtsf2",
+        BinaryOp::Le => "__lesf2",
+        BinaryOp::Ge => "__gesf2",
+        _ => unreachable!(),
+    };
+    let result = generate_float_call(program, scopes, routine, vec![lhs, rhs]);
+    let zero = cur_func!(scopes).new_value(program).integer(0);
+    generate_binary(program, scopes, op, result, zero)
+}
+
+/// Generates a call to the given soft-float routine of libgcc,
+/// which the backend replaces with F extension instructions.
+fn generate_float_call(
+    program: &mut Program,
+    scopes: &Scopes,
+    routine: &str,
+    args: Vec<Value>,
+) -> Value {
+    // routines are declared with the runtime library
+    let Ok((callee, _)) = scopes.func(routine) else {
+        unreachable!()
+    };
+    let info = cur_func!(scopes);
+    let call = info.new_value(program).call(callee, args);
+    info.push_inst(program, call);
+    call
+}
+
+/// Generates a binary instruction in the current basic block.
+fn generate_binary(
+    program: &mut Program,
+    scopes: &Scopes,
+    op: BinaryOp,
+    lhs: Value,
+    rhs: Value,
+) -> Value {
+    let info = cur_func!(scopes);
+    let value = info.new_value(program).binary(op, lhs, rhs);
+    info.push_inst(program, value);
+    value
+}
+
+/// Converts the given number to a boolean value (`value != 0`).
+fn generate_bool(program: &mut Program, scopes: &Scopes, value: Value, ty: BType) -> Value {
+    let zero = cur_func!(scopes).new_value(program).integer(0);
+    match ty {
+        BType::Int => generate_binary(program, scopes, BinaryOp::NotEq, value, zero),
+        // the bit pattern of `0.0` is also 0
+        BType::Float => generate_float_cmp(program, scopes, BinaryOp::NotEq, value, zero),
+    }
+}
+
+/// Generates a short-circuit logical expression (`lhs && rhs` if `is_and`
+/// is `true`, otherwise `lhs || rhs`).
+///
+/// The right-hand side is evaluated in a separate basic block, which is
+/// skipped if the left-hand side already decides the result.
+fn generate_logical<'ast, L, R>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    lhs: &'ast L,
+    rhs: &'ast R,
+    is_and: bool,
+) -> Result<ExpValue>
+where
+    L: GenerateProgram<'ast, Out = ExpValue> + Spanned,
+    R: GenerateProgram<'ast, Out = ExpValue> + Spanned,
+{
+    let prefix = if is_and { "%land" } else { "%lor" };
+
+    // generate result
+    let result = cur_func!(scopes).new_alloc(program, Type::get_i32(), None);
+
+    // generate left-hand side expression
+    let (lhs, ty) = generate_operand(program, scopes, lhs)?;
+    let lhs = generate_bool(program, scopes, lhs, ty);
+    let info = cur_func!(scopes);
+    let store = info.new_value(program).store(lhs, result);
+    info.push_inst(program, store);
+
+    // generate branch
+    let rhs_bb = info.new_bb(program, Some(&format!("{prefix}_rhs")));
+    let end_bb = info.new_bb(program, Some(&format!("{prefix}_end")));
+    let (true_bb, false_bb) = if is_and { (rhs_bb, end_bb) } else { (end_bb, rhs_bb) };
+    let br = info.new_value(program).branch(lhs, true_bb, false_bb);
+    info.push_inst(program, br);
+
+    // generate right-hand side expression
+    cur_func_mut!(scopes).push_bb(program, rhs_bb);
+    let (rhs, ty) = generate_operand(program, scopes, rhs)?;
+    let rhs = generate_bool(program, scopes, rhs, ty);
+    let info = cur_func_mut!(scopes);
+    let store = info.new_value(program).store(rhs, result);
+    info.push_inst(program, store);
+    let jump = info.new_value(program).jump(end_bb);
+    info.push_inst(program, jump);
+
+    // load the result in the end basic block
+    info.push_bb(program, end_bb);
+    let load = info.new_value(program).load(result);
+    info.push_inst(program, load);
+    Ok(ExpValue::Int(load))
+}
+
+/// Generates a jump to the given basic block, and pushes a new basic block
+/// for the (unreachable) code that follows.
+fn generate_jump(program: &mut Program, scopes: &mut Scopes, target: BasicBlock) {
+    let info = cur_func_mut!(scopes);
+    let jump = info.new_value(program).jump(target);
+    info.push_inst(program, jump);
+    let next = info.new_bb(program, None);
+    info.push_bb(program, next);
+}
+
+/// Generates the given block items, warns about unreachable code
+/// after `return`, `break` and `continue`.
+fn generate_items<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    items: &'ast [BlockItem],
+) -> Result<()> {
+    let mut jump = None;
+    for item in items {
+        if let Some(stmt) = jump.take() {
+            let span = match item {
+                BlockItem::Decl(Decl::Const(decl)) => decl.defs[0].span,
+                BlockItem::Decl(Decl::Var(decl)) => decl.defs[0].span,
+                BlockItem::Stmt(stmt) => stmt_span(stmt),
+            };
+            scopes.diags.warn(WarningKind::Unreachable(stmt).at(span));
+        }
+        item.generate(program, scopes)?;
+        // `break` and `continue` outside loops have been reported as errors
+        let in_loop = scopes.loop_info().is_some();
+        jump = match item {
+            BlockItem::Stmt(Stmt::Return(_)) => Some("return"),
+            BlockItem::Stmt(Stmt::Break(_)) if in_loop => Some("break"),
+            BlockItem::Stmt(Stmt::Continue(_)) if in_loop => Some("continue"),
+            _ => jump,
+        };
+    }
+    Ok(())
+}
+
+/// Returns the span of the given statement.
+fn stmt_span(stmt: &Stmt) -> Span {
+    match stmt {
+        Stmt::Assign(s) => s.span,
+        Stmt::ExpStmt(s) => s.span,
+        Stmt::Block(s) => s.span,
+        Stmt::If(s) => s.span,
+        Stmt::While(s) => s.span,
+        Stmt::Break(s) => s.span,
+        Stmt::Continue(s) => s.span,
+        Stmt::Return(s) => s.span,
+    }
+}
+
+/// Returns the function call if the given expression is just a call.
+fn as_call(exp: &Exp) -> Option<&FuncCall> {
+    match &*exp.lor {
+        LOrExp::LAnd(LAndExp::Eq(EqExp::Rel(RelExp::Add(AddExp::Mul(MulExp::Unary(
+            UnaryExp::Call(call),
+        )))))) => Some(call),
+        _ => None,
+    }
+}
+
+/// Warns if the given identifier shadows a symbol in an outer scope.
+fn check_shadowing(scopes: &mut Scopes, id: &str, span: Span) {
+    if scopes.is_shadowing(id) {
+        scopes.diags.warn(WarningKind::Shadow(id.into()).at(span));
+    }
+}
+
+/// Generates a constant definition of the given base type.
+fn generate_const_def<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    ty: BType,
+    def: &'ast ConstDef,
+) -> Result<()> {
+    let dims = eval_dims(scopes, &def.dims)?;
+    let elems = def.init.generate(program, scopes)?.flatten(&dims)?;
+    let nums: Vec<_> = elems
+        .iter()
+        .map(|elem| match elem {
+            Initializer::Const(num) => num.to(ty),
+            _ => unreachable!(),
+        })
+        .collect();
+    let symbol = if dims.is_empty() {
+        Symbol::Const(nums[0])
+    } else {
+        // constant arrays are stored in memory, since they can be
+        // indexed by non-constant expressions
+        let elems = nums.iter().map(|&num| Initializer::Const(num)).collect();
+        let alloc = generate_alloc(program, scopes, &def.ident, &dims, Some(elems));
+        Symbol::ConstArray(alloc, Rc::new(ConstElems { ty, dims, elems: nums }))
+    };
+    check_shadowing(scopes, &def.ident, def.span);
+    scopes.new_value(&def.ident, symbol)
+}
+
+/// Generates a variable definition of the given base type,
+/// elements of the initializer are converted to the type.
+fn generate_var_def<'ast>(
+    program: &mut Program,
+    scopes: &mut Scopes<'ast>,
+    ty: BType,
+    def: &'ast VarDef,
+) -> Result<()> {
+    let dims = eval_dims(scopes, &def.dims)?;
+    let elems = match &def.init {
+        Some(init) => {
+            let elems = init.generate(program, scopes)?.flatten(&dims)?;
+            let elems = elems.into_iter().map(|elem| match elem {
+                Initializer::Const(num) => Initializer::Const(num.to(ty)),
+                Initializer::Value(value, from) => {
+                    Initializer::Value(generate_conversion(program, scopes, value, from, ty), ty)
+                }
+                Initializer::List(_) => unreachable!(),
+            });
+            Some(elems.collect())
+        }
+        None => None,
+    };
+    let alloc = generate_alloc(program, scopes, &def.ident, &dims, elems);
+    check_shadowing(scopes, &def.ident, def.span);
+    scopes.new_value(&def.ident, Symbol::Var(alloc, ty))?;
+    if !scopes.is_global() {
+        scopes.track_unused(&def.ident, def.span);
+    }
+    Ok(())
+}
+
+/// Evaluates the dimensions of an array.
+fn eval_dims(scopes: &Scopes, dims: &[ConstExp]) -> Result<Vec<usize>> {
+    dims.iter()
+        .map(|dim| match dim.eval(scopes) {
+            Ok(Num::Int(len)) if len > 0 => Ok(len as usize),
+            Ok(_) => Err(ErrorKind::InvalidArrayLen.at(dim.exp.span)),
+            Err(e) if matches!(e.kind, ErrorKind::FailedToEval) => {
+                Err(ErrorKind::NonConstArrayLen.at(dim.exp.span))
+            }
+            Err(e) => Err(e.or_span(dim.exp.span)),
+        })
+        .collect()
+}
+
+/// Generates the allocation of a variable (or constant array) with the
+/// given dimensions, and its initializer (flattened) if any.
+fn generate_alloc(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    ident: &str,
+    dims: &[usize],
+    elems: Option<Vec<Initializer>>,
+) -> Value {
+    let ty = array_type(dims);
+    if scopes.is_global() {
+        // generate global allocation
+        let init = match elems {
+            Some(elems) if elems.iter().any(|e| !is_zero(e)) => {
+                generate_aggregate(program, dims, &elems)
+            }
+            _ => program.new_value().zero_init(ty),
+        };
+        let alloc = program.new_value().global_alloc(init);
+        program.set_value_name(alloc, Some(format!("@{ident}")));
+        alloc
+    } else {
+        // generate local allocation
+        let info = cur_func_mut!(scopes);
+        let alloc = info.new_alloc(program, ty, Some(ident));
+        let elems = match elems {
+            Some(elems) if !dims.is_empty() => elems,
+            Some(elems) => {
+                let value = match elems[0] {
+                    Initializer::Const(num) => info.new_value(program).integer(num.bits()),
+                    Initializer::Value(value, _) => value,
+                    Initializer::List(_) => unreachable!(),
+                };
+                let store = info.new_value(program).store(value, alloc);
+                info.push_inst(program, store);
+                return alloc;
+            }
+            None => return alloc,
+        };
+
+        // get the pointer to the first element, elements are stored by offsets to it
+        let mut first = alloc;
+        for _ in dims {
+            let zero = info.new_value(program).integer(0);
+            first = info.new_value(program).get_elem_ptr(first, zero);
+            info.push_inst(program, first);
+        }
+
+        // fill zeros by a loop if there are many of them, then store other elements
+        let zeros = elems.iter().filter(|e| is_zero(e)).count();
+        let fill_zeros = zeros >= ZERO_FILL_MIN_LEN;
+        if fill_zeros {
+            generate_zero_fill(program, info, first, elems.len());
+        }
+        for (i, elem) in elems.into_iter().enumerate() {
+            let value = match elem {
+                Initializer::Const(num) if fill_zeros && num.bits() == 0 => continue,
+                Initializer::Const(num) => info.new_value(program).integer(num.bits()),
+                Initializer::Value(value, _) => value,
+                Initializer::List(_) => unreachable!(),
+            };
+            let ptr = if i == 0 {
+                first
+            } else {
+                let index = info.new_value(program).integer(i as i32);
+                let ptr = info.new_value(program).get_ptr(first, index);
+                info.push_inst(program, ptr);
+                ptr
+            };
+            let store = info.new_value(program).store(value, ptr);
             info.push_inst(program, store);
         }
+        alloc
+    }
+}
 
-        // jump to the end basic block
-        let info = &mut cur_func_mut!(scopes);
-        let jump = info.new_value(program).jump(info.end());
-        info.push_inst(program, jump);
+/// Minimum number of zero elements to fill by a loop
+/// instead of storing them one by one.
+const ZERO_FILL_MIN_LEN: usize = 16;
 
-        // push new basic block
-        let next = info.new_bb(program, None);
-        info.push_bb(program, next);
-        Ok(())
+/// Generates a loop that stores zeros to `len` elements starting from `ptr`.
+fn generate_zero_fill(program: &mut Program, info: &mut FunctionInfo, ptr: Value, len: usize) {
+    let loop_bb = info.new_bb_with_params(program, Some("%zero_fill"), vec![Type::get_i32()]);
+    let end_bb = info.new_bb(program, Some("%zero_fill_end"));
+    let zero = info.new_value(program).integer(0);
+    let jump = info.new_value(program).jump_with_args(loop_bb, vec![zero]);
+    info.push_inst(program, jump);
+
+    // store zero to the element at `index`, then move to the next one
+    info.push_bb(program, loop_bb);
+    let index = program.func(info.func()).dfg().bb(loop_bb).params()[0];
+    let elem = info.new_value(program).get_ptr(ptr, index);
+    info.push_inst(program, elem);
+    let zero = info.new_value(program).integer(0);
+    let store = info.new_value(program).store(zero, elem);
+    info.push_inst(program, store);
+    let one = info.new_value(program).integer(1);
+    let next = info.new_value(program).binary(BinaryOp::Add, index, one);
+    info.push_inst(program, next);
+    let len = info.new_value(program).integer(len as i32);
+    let cond = info.new_value(program).binary(BinaryOp::Lt, next, len);
+    info.push_inst(program, cond);
+    let br = info.new_value(program).branch_with_args(cond, loop_bb, end_bb, vec![next], vec![]);
+    info.push_inst(program, br);
+    info.push_bb(program, end_bb);
+}
+
+/// Generates the constant aggregate of a global array
+/// with the given dimensions and flattened elements.
+fn generate_aggregate(program: &mut Program, dims: &[usize], elems: &[Initializer]) -> Value {
+    match dims.split_first() {
+        Some((&len, sub_dims)) => {
+            let elems = elems
+                .chunks(elems.len() / len)
+                .map(|elems| generate_aggregate(program, sub_dims, elems))
+                .collect();
+            program.new_value().aggregate(elems)
+        }
+        None => match elems[0] {
+            Initializer::Const(num) => program.new_value().integer(num.bits()),
+            _ => unreachable!(),
+        },
     }
 }
+
+/// Returns `true` if the given element of a flattened initializer
+/// is a constant whose bit pattern is 0.
+fn is_zero(elem: &Initializer) -> bool {
+    matches!(elem, Initializer::Const(num) if num.bits() == 0)
+}
+
+/// Returns the type of an array with the given dimensions,
+/// or `i32` if there are no dimensions.
+fn array_type(dims: &[usize]) -> Type {
+    dims.iter()
+        .rev()
+        .fold(Type::get_i32(), |ty, &len| Type::get_array(ty, len))
+}
+
+/// Returns `true` if the given type is a pointer to a pointer.
+fn is_ptr_ptr(ty: &Type) -> bool {
+    matches!(ty.kind(), TypeKind::Pointer(base) if matches!(base.kind(), TypeKind::Pointer(_)))
+}
+
+/// Returns `true` if the given type is a pointer to an array.
+fn is_array_ptr(ty: &Type) -> bool {
+    matches!(ty.kind(), TypeKind::Pointer(base) if matches!(base.kind(), TypeKind::Array(..)))
+}
@@ -1,30 +1,702 @@
+mod eval;
 mod func;
 mod gen;
 mod scopes;
+mod values;
 
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
-use crate::ast::CompUnit;
+use crate::ast::{BType, CompUnit, Span};
 
 use gen::GenerateProgram;
-use koopa::ir::Program;
-use scopes::Scopes;
+use koopa::ir::{FunctionData, Program, Type, TypeKind, ValueKind};
+use scopes::{FuncSig, Scopes};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// Generates Koopa IR program for the given compile unit (ASTs).
-pub fn generate_program(comp_unit: &CompUnit) -> Result<Program> {
+/// Generates Koopa IR program for the given compile unit (ASTs),
+/// returns the program, signatures of functions that take or return
+/// `float`s, and all diagnostics reported during generation.
+///
+/// The program is only valid if there are no errors in the diagnostics.
+pub fn generate_program(comp_unit: &CompUnit) -> (Program, FloatSigs, Diagnostics) {
     let mut program = Program::new();
-    comp_unit.generate(&mut program, &mut Scopes::new())?;
-    Ok(program)
+    let mut scopes = Scopes::new();
+    if let Err(err) = decl_lib_funcs(&mut program, &mut scopes) {
+        scopes.diags.error(err);
+    }
+    if let Err(err) = comp_unit.generate(&mut program, &mut scopes) {
+        scopes.diags.error(err);
+    }
+    let float_sigs = scopes
+        .funcs()
+        .filter_map(|(func, sig)| {
+            let data = program.func(*func);
+            let params_ty = match data.ty().kind() {
+                TypeKind::Function(params_ty, _) => params_ty,
+                _ => unreachable!(),
+            };
+            // array parameters are passed as pointers
+            let params = params_ty
+                .iter()
+                .zip(&sig.params)
+                .map(|(ty, &bty)| bty == BType::Float && ty.is_i32())
+                .collect();
+            let sig = FloatSig {
+                params,
+                ret: sig.ret == Some(BType::Float),
+            };
+            (sig.ret || sig.params.contains(&true)).then(|| (data.name().into(), sig))
+        })
+        .collect();
+    remove_unused_routines(&mut program, &scopes);
+    (program, float_sigs, scopes.diags)
+}
+
+/// Signatures of functions that take or return `float`s, by their names
+/// (including the `@` prefix).
+///
+/// `float`s are represented by their bit patterns in Koopa IR, so these
+/// signatures are needed for passing them in floating-point registers.
+pub type FloatSigs = HashMap<String, FloatSig>;
+
+/// Which parameters and whether the return value of a function are `float`s.
+pub struct FloatSig {
+    pub params: Vec<bool>,
+    pub ret: bool,
+}
+
+/// Declares functions in the SysY runtime library.
+fn decl_lib_funcs(program: &mut Program, scopes: &mut Scopes) -> Result<()> {
+    let int = || (Type::get_i32(), BType::Int);
+    let float = || (Type::get_i32(), BType::Float);
+    let int_ptr = || (Type::get_pointer(Type::get_i32()), BType::Int);
+    let float_ptr = || (Type::get_pointer(Type::get_i32()), BType::Float);
+    let lib_funcs = [
+        ("getint", vec![], Some(BType::Int)),
+        ("getch", vec![], Some(BType::Int)),
+        ("getfloat", vec![], Some(BType::Float)),
+        ("getarray", vec![int_ptr()], Some(BType::Int)),
+        ("getfarray", vec![float_ptr()], Some(BType::Int)),
+        ("putint", vec![int()], None),
+        ("putch", vec![int()], None),
+        ("putfloat", vec![float()], None),
+        ("putarray", vec![int(), int_ptr()], None),
+        ("putfarray", vec![int(), float_ptr()], None),
+        ("starttime", vec![], None),
+        ("stoptime", vec![], None),
+    ];
+    for (name, params, ret) in lib_funcs {
+        let (params_ty, params) = params.into_iter().unzip();
+        let ret_ty = ret.map_or_else(Type::get_unit, |_| Type::get_i32());
+        let data = FunctionData::new_decl(format!("@{name}"), params_ty, ret_ty);
+        let func = program.new_func(data);
+        scopes.new_func(name, func, FuncSig { params, ret })?;
+    }
+
+    // soft-float routines are declared before any function that calls them
+    for (name, params_len) in FLOAT_ROUTINES {
+        let params_ty = vec![Type::get_i32(); params_len];
+        let data = FunctionData::new_decl(format!("@{name}"), params_ty, Type::get_i32());
+        let func = program.new_func(data);
+        let sig = FuncSig {
+            params: vec![BType::Int; params_len],
+            ret: Some(BType::Int),
+        };
+        scopes.new_func(name, func, sig)?;
+    }
+    Ok(())
+}
+
+/// Soft-float routines of libgcc and their numbers of parameters,
+/// operations on `float`s are calls to them.
+const FLOAT_ROUTINES: [(&str, usize); 13] = [
+    ("__addsf3", 2),
+    ("__subsf3", 2),
+    ("__mulsf3", 2),
+    ("__divsf3", 2),
+    ("__negsf2", 1),
+    ("__eqsf2", 2),
+    ("__nesf2", 2),
+    ("__ltsf2", 2),
+    ("__gtsf2", 2),
+    ("__lesf2", 2),
+    ("__gesf2", 2),
+    ("__floatsisf", 1),
+    ("__fixsfsi", 1),
+];
+
+/// Removes declarations of soft-float routines that are never called.
+fn remove_unused_routines(program: &mut Program, scopes: &Scopes) {
+    let called: HashSet<_> = program
+        .funcs()
+        .values()
+        .flat_map(|data| data.dfg().values().values())
+        .filter_map(|value| match value.kind() {
+            ValueKind::Call(call) => Some(call.callee()),
+            _ => None,
+        })
+        .collect();
+    for (name, _) in FLOAT_ROUTINES {
+        match scopes.func(name) {
+            Ok((func, _)) if !called.contains(&func) => {
+                program.remove_func(func);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Errors and warnings reported by IR generator.
+#[derive(Default)]
+pub struct Diagnostics {
+    errors: Vec<Error>,
+    warnings: Vec<Warning>,
+}
+
+impl Diagnostics {
+    /// Reports an error.
+    pub fn error(&mut self, err: Error) {
+        self.errors.push(err);
+    }
+
+    /// Reports a warning.
+    pub fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    /// Removes warnings whose names are not accepted by the given filter.
+    pub fn retain_warnings(&mut self, enabled: impl Fn(&str) -> bool) {
+        self.warnings.retain(|w| enabled(w.kind.name()));
+    }
+
+    /// Returns `true` if any errors have been reported.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Returns all diagnostics in the form of `(level, message, span)`,
+    /// ordered by their positions in the source.
+    pub fn sorted(&self) -> Vec<(&'static str, String, Option<Span>)> {
+        let errors = self.errors.iter().map(|e| ("error", e.to_string(), e.span));
+        let warnings = self.warnings.iter().map(|w| ("warning", w.to_string(), w.span));
+        let mut diags: Vec<_> = errors.chain(warnings).collect();
+        diags.sort_by_key(|(_, _, span)| span.map(|s| s.start));
+        diags
+    }
 }
 
 /// Error returned by IR generator.
-pub enum Error {
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Span of the source that caused the error, if known.
+    pub span: Option<Span>,
+}
+
+impl Error {
+    /// Attaches the given span to the error if it does not have one.
+    pub fn or_span(self, span: Span) -> Self {
+        Self {
+            span: self.span.or(Some(span)),
+            ..self
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self { kind, span: None }
+    }
 }
 
 impl fmt::Display for Error {
-    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// Kind of IR generator errors.
+pub enum ErrorKind {
+    DuplicatedDef(String),
+    SymbolNotFound(String),
+    FailedToEval,
+    AssignToConst(String),
+    CallNonFunc(String),
+    /// Function name, number of parameters and number of arguments.
+    ArgCountMismatch(String, usize, usize),
+    ArgTypeMismatch(String),
+    NotInLoop(&'static str),
+    UseVoidValue,
+    InvalidArrayLen,
+    NonConstArrayLen,
+    InvalidInit,
+    DimMismatch(String),
+    NotAnLValue,
+    NonIntCalc,
+    PtrAsInt,
+    RetValInVoidFunc(String),
+    NoRetValInIntFunc(String),
+    FloatMod,
+    NonIntIndex,
+}
+
+impl ErrorKind {
+    /// Creates an error of this kind at the given span.
+    pub fn at(self, span: Span) -> Error {
+        Error {
+            kind: self,
+            span: Some(span),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicatedDef(id) => write!(f, "redefinition of `{id}`"),
+            Self::SymbolNotFound(id) => write!(f, "use of undeclared identifier `{id}`"),
+            Self::FailedToEval => write!(f, "expression is not a compile-time constant"),
+            Self::AssignToConst(id) => write!(f, "cannot assign to constant `{id}`"),
+            Self::CallNonFunc(id) => write!(f, "called object `{id}` is not a function"),
+            Self::ArgCountMismatch(id, params, args) => write!(
+                f,
+                "function `{id}` takes {params} argument(s) but {args} were given"
+            ),
+            Self::ArgTypeMismatch(id) => write!(f, "mismatched argument type when calling `{id}`"),
+            Self::NotInLoop(stmt) => write!(f, "`{stmt}` statement not within a loop"),
+            Self::UseVoidValue => write!(f, "using the value of a `void` expression"),
+            Self::InvalidArrayLen => write!(f, "array length must be a positive integer"),
+            Self::NonConstArrayLen => write!(f, "array length is not a compile-time constant"),
+            Self::InvalidInit => write!(f, "invalid initializer"),
+            Self::DimMismatch(id) => write!(f, "dimension mismatch when indexing `{id}`"),
+            Self::NotAnLValue => write!(f, "expression is not assignable"),
+            Self::NonIntCalc => write!(f, "using an array as an integer"),
+            Self::PtrAsInt => write!(f, "using a pointer as an integer"),
+            Self::RetValInVoidFunc(id) => {
+                write!(f, "void function `{id}` should not return a value")
+            }
+            Self::NoRetValInIntFunc(id) => {
+                write!(f, "non-void function `{id}` should return a value")
+            }
+            Self::FloatMod => write!(f, "invalid operands of type `float` to `%`"),
+            Self::NonIntIndex => write!(f, "array subscript is not an integer"),
+        }
+    }
+}
+
+/// Warning reported by IR generator.
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [-W{}]", self.kind, self.kind.name())
+    }
+}
+
+/// Kind of IR generator warnings.
+pub enum WarningKind {
+    UnusedVar(String),
+    UnusedResult(String),
+    Unreachable(&'static str),
+    DivByZero,
+    Shadow(String),
+    ImplicitReturn(String),
+}
+
+impl WarningKind {
+    /// Names of all warnings and whether they are enabled by default,
+    /// the names can be used in `-W<name>` and `-Wno-<name>`.
+    pub const ALL: &'static [(&'static str, bool)] = &[
+        ("unused-variable", true),
+        ("unused-result", false),
+        ("unreachable-code", true),
+        ("div-by-zero", true),
+        ("shadow", false),
+        ("return-type", true),
+    ];
+
+    /// Returns the name of the warning.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UnusedVar(_) => "unused-variable",
+            Self::UnusedResult(_) => "unused-result",
+            Self::Unreachable(_) => "unreachable-code",
+            Self::DivByZero => "div-by-zero",
+            Self::Shadow(_) => "shadow",
+            Self::ImplicitReturn(_) => "return-type",
+        }
+    }
+
+    /// Creates a warning of this kind at the given span.
+    pub fn at(self, span: Span) -> Warning {
+        Warning {
+            kind: self,
+            span: Some(span),
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnusedVar(id) => write!(f, "unused variable `{id}`"),
+            Self::UnusedResult(id) => write!(f, "result of calling `{id}` is unused"),
+            Self::Unreachable(stmt) => write!(f, "unreachable code after `{stmt}`"),
+            Self::DivByZero => write!(f, "division by zero"),
+            Self::Shadow(id) => {
+                write!(f, "declaration of `{id}` shadows a previous declaration")
+            }
+            Self::ImplicitReturn(id) => write!(
+                f,
+                "control reaches end of non-void function `{id}`, returning 0"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use koopa::ir::builder_traits::*;
+    use koopa::back::KoopaGenerator;
+    use crate::code::testing::{compile, koopa, run};
+    use crate::code::RegAlloc;
+    use crate::sysy::CompUnitParser;
+
+    /// Compiles the given program, runs it with the given input,
+    /// returns the output and the exit code.
+    fn exec(text: &str, input: &str) -> (String, i32) {
+        run(&compile(text, RegAlloc::None), input)
+    }
+
+    /// Returns diagnostics of the given program, as `(level, message, source text of span)`.
+    fn diags(text: &str) -> Vec<(&'static str, String, &str)> {
+        let comp_unit = CompUnitParser::new().parse(&mut Vec::new(), text).unwrap();
+        let (_, _, diags) = generate_program(&comp_unit);
+        diags
+            .sorted()
+            .into_iter()
+            .map(|(level, msg, span)| (level, msg, span.map_or("", |s| &text[s.start..s.end])))
+            .collect()
+    }
+
+    #[test]
+    fn float_ops() {
+        let text = r#"
+const float PI = 3.14159, A[2] = {1, 2.5};
+const int N = 2.9 * 2;
+float g[3] = {1.5, 2};
+float half(float x) { return x / 2; }
+int trunc(float x) { return x; }
+float mixed(float a, int b, float c, float d, float e, float f, float g, float h, float i,
+            float j, int k, float l) {
+  return a + b + c + d + e + f + g + h + i + j + k + l;
+}
+float many(float a, float b, float c, float d, float e, float f, float g, float h, float i,
+           float j, float k, float l, float m, float n, float o, float p, float q, float r) {
+  return a - r + q * 2;
+}
+float sum(float a[], int n) {
+  int i = 0;
+  float s = 0;
+  while (i < n) {
+    s = s + a[i];
+    i = i + 1;
+  }
+  return s;
+}
+int main() {
+  float x = getfloat();
+  int n = 7;
+  putfloat(half(x));
+  putch(32);
+  putint(trunc(-2.7));
+  putch(32);
+  putfloat(n / 2);
+  putch(32);
+  putfloat(n / 2.0);
+  putch(32);
+  putfloat(mixed(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12));
+  putch(32);
+  putfloat(many(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18));
+  putch(32);
+  putfloat(sum(g, 3) + A[1]);
+  putch(32);
+  putint(x > 2.5);
+  putint(!x);
+  putint(x == 3);
+  putint(x && 0.1);
+  putint(0.0 || x < -x);
+  putch(32);
+  float a[2][2] = {{x}, {n, 0.5}};
+  putfloat(a[0][0] * a[1][0] - a[1][1] + -x);
+  putch(32);
+  int m = PI * 100;
+  putint(m);
+  putint(N);
+  return x;
+}
+"#;
+        let output = "0x1.8p+0 -2 0x1.8p+1 0x1.cp+1 0x1.38p+6 0x1.1p+4 0x1.8p+2 \
+                      10110 0x1.18p+4 3145";
+        for reg_alloc in [RegAlloc::None, RegAlloc::LinearScan, RegAlloc::GraphColoring] {
+            assert_eq!(run(&compile(text, reg_alloc), "3"), (output.into(), 3));
+        }
+    }
+
+    #[test]
+    fn float_routines_declared_before_use() {
+        let text = "float f(float x) { return x * 2; } int main() { return f(1); }";
+        let comp_unit = CompUnitParser::new().parse(&mut Vec::new(), text).unwrap();
+        let (program, _, _) = generate_program(&comp_unit);
+        let mut ir = Vec::new();
+        KoopaGenerator::new(&mut ir).generate_on(&program).unwrap();
+        let ir = String::from_utf8(ir).unwrap();
+        assert!(ir.contains("decl @__mulsf3(i32, i32): i32"));
+        assert!(!ir.contains("@__addsf3"));
+        koopa(&ir);
+    }
+
+    #[test]
+    fn float_literals() {
+        let text = r#"
+int main() {
+  putfloat(.5);
+  putch(32);
+  putfloat(1.e1);
+  putch(32);
+  putfloat(0x1.8p1);
+  putch(32);
+  putfloat(0X.1P-2);
+  putch(32);
+  putfloat(0x1.000001p0);
+  putch(32);
+  putfloat(0x1p-149);
+  return 0;
+}
+"#;
+        let output = "0x1p-1 0x1.4p+3 0x1.8p+1 0x1p-6 0x1p+0 0x1p-149";
+        assert_eq!(exec(text, ""), (output.into(), 0));
+    }
+
+    #[test]
+    fn float_errors() {
+        let diags = diags(
+            "const float c = 1.5 % 2; int a[1.0];\n\
+             int main() { float x = 1; int b[2]; b[x] = 1; return x % 2; }",
+        );
+        let errors: Vec<_> = diags.iter().map(|(_, msg, span)| (msg.as_str(), *span)).collect();
+        assert_eq!(
+            errors,
+            [
+                ("invalid operands of type `float` to `%`", "1.5 % 2"),
+                ("array length must be a positive integer", "1.0"),
+                ("array subscript is not an integer", "x"),
+                ("invalid operands of type `float` to `%`", "x % 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_warnings_after_errors() {
+        let diags = diags(
+            "void v() {} int f() { return v(); } void g() { return 1; }\n\
+             int main() { break; continue; return 0; }",
+        );
+        assert!(diags.iter().all(|(level, _, _)| *level == "error"));
+        assert_eq!(diags.len(), 4);
+    }
+
+    #[test]
+    fn unreachable_after_break() {
+        let diags = diags("int main() { while (1) { break; return 1; } return 0; }");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].1, "unreachable code after `break` [-Wunreachable-code]");
+        assert_eq!(diags[0].2, "return 1;");
+    }
+
+    #[test]
+    fn zero_fill_local_array() {
+        let text = "int main() { int a[100][100] = {1}; return a[1][2]; }";
+        let comp_unit = CompUnitParser::new().parse(&mut Vec::new(), text).unwrap();
+        let (program, _, _) = generate_program(&comp_unit);
+        let main = program.funcs().values().find(|f| f.name() == "@main").unwrap();
+        let insts: usize = main.layout().bbs().nodes().map(|bb| bb.insts().len()).sum();
+        assert!(insts < 50, "{insts} instructions");
+    }
+
+    #[test]
+    fn fold_const_array() {
+        let text = "const int a[2] = {1, 2}, m[2][3] = {{1}, {4, 5, 6}};\n\
+                    const int c = a[1] + m[1][a[0]];\n\
+                    int g[c] = {m[1][2]};\n\
+                    int main() { int b[a[1]]; const int d[m[1][0]] = {}; return g[0] + b[0] + d[0]; }";
+        assert!(diags(text).iter().all(|(level, _, _)| *level != "error"));
+
+        let diags = diags("const int a[2] = {1, 2}; int b[a[2]]; int main() { return 0; }");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].1, "array length is not a compile-time constant");
+        assert_eq!(diags[0].2, "a[2]");
+    }
+
+    #[test]
+    fn operand_spans() {
+        let diags = diags(
+            "void v() {}\n\
+             int main() { int a[2], x = 1; x = 1 + v() * 2; x = -v(); if (x && a) x = 2; \
+             x = (x < v()) == 1; return a[v()] + a; }",
+        );
+        let spans: Vec<_> = diags.iter().map(|(_, msg, span)| (msg.as_str(), *span)).collect();
+        assert_eq!(
+            spans,
+            [
+                ("using the value of a `void` expression", "v()"),
+                ("using the value of a `void` expression", "v()"),
+                ("using an array as an integer", "a"),
+                ("using the value of a `void` expression", "v()"),
+                ("using the value of a `void` expression", "v()"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unary_pos_no_zero() {
+        let text = "int main() { int a = 1; return +a; }";
+        let comp_unit = CompUnitParser::new().parse(&mut Vec::new(), text).unwrap();
+        let (program, _, _) = generate_program(&comp_unit);
+        let main = program.funcs().values().find(|f| f.name() == "@main").unwrap();
+        let unused_ints = main.dfg().values().values().filter(|v| {
+            matches!(v.kind(), koopa::ir::ValueKind::Integer(_)) && v.used_by().is_empty()
+        });
+        assert_eq!(unused_ints.count(), 0);
+    }
+
+    #[test]
+    fn short_circuit() {
+        let text = r#"
+int n = 0;
+int f(int x) { n = n + 1; putint(x); return x; }
+int main() {
+  if (f(0) && f(1)) putch(33);
+  putch(32);
+  if (f(1) || f(2)) putch(33);
+  putch(32);
+  int a = f(1) && f(0) || f(3);
+  putch(32);
+  putint(a);
+  putint(n);
+  return !(f(0) || f(0)) && f(4);
+}
+"#;
+        assert_eq!(exec(text, ""), ("0 1! 103 15004".into(), 1));
+    }
+
+    #[test]
+    fn if_else() {
+        let text = r#"
+int sign(int x) {
+  if (x > 0) return 1;
+  else if (x < 0) return -1;
+  return 0;
+}
+int main() {
+  int a = getint();
+  if (a) if (a > 5) putint(1); else putint(2);
+  putch(32);
+  putint(sign(a));
+  putint(sign(-a));
+  if (a == 3) {
+    putint(3);
+  } else {
+    putint(4);
+  }
+  return 0;
+}
+"#;
+        // `else` belongs to the nearest `if`
+        assert_eq!(exec(text, "3"), ("2 1-13".into(), 0));
+        assert_eq!(exec(text, "0"), (" 004".into(), 0));
+    }
+
+    #[test]
+    fn while_break_continue() {
+        let text = r#"
+int main() {
+  int i = 0, s = 0;
+  while (i < 10) {
+    i = i + 1;
+    if (i % 2 == 0) continue;
+    if (i > 7) break;
+    int j = 0;
+    while (1) {
+      j = j + 1;
+      if (j >= i) break;
+    }
+    s = s + j;
+  }
+  putint(s);
+  putch(32);
+  putint(i);
+  return 0;
+}
+"#;
+        assert_eq!(exec(text, ""), ("16 9".into(), 0));
+
+        let diags = diags("int main() { if (1) break; continue; }");
+        let errors: Vec<_> = diags.iter().map(|(_, msg, span)| (msg.as_str(), *span)).collect();
+        assert_eq!(
+            errors,
+            [
+                ("`break` statement not within a loop", "break;"),
+                ("`continue` statement not within a loop", "continue;"),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_int_literals() {
+        let text = r#"
+int main() {
+  putint(-2147483648);
+  putch(32);
+  putint(2147483648);
+  putch(32);
+  putint(0xFFFFFFFF + 037777777777);
+  putch(32);
+  putint(-0x80000000 == 0x80000000);
+  return 0;
+}
+"#;
+        assert_eq!(exec(text, ""), ("-2147483648 -2147483648 -2 1".into(), 0));
+    }
+
+    #[test]
+    fn ptr_as_int() {
+        let mut func = FunctionData::new("@f".into(), vec![], Type::get_unit());
+        let ptr = func.dfg_mut().new_value().alloc(Type::get_i32());
+        let err = values::ExpValue::Ptr(ptr, BType::Int).into_num().err().unwrap();
+        assert_eq!(err.to_string(), "using a pointer as an integer");
+    }
+
+    #[test]
+    fn redefine_across_namespaces() {
+        let diags = diags(
+            "int f; int f() { return 0; }\n\
+             int g() { return 0; } int g;\n\
+             int main() { int h = 1; h(2); return 0; }",
+        );
+        let spans: Vec<_> = diags.iter().map(|(l, msg, span)| (*l, msg.as_str(), *span)).collect();
+        assert_eq!(
+            spans,
+            [
+                ("error", "redefinition of `f`", "int f() { return 0; }"),
+                ("error", "redefinition of `g`", "g"),
+                ("error", "called object `h` is not a function", "h(2)"),
+            ]
+        );
     }
 }
@@ -1,7 +1,22 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use koopa::ir::{BasicBlock, Function, Program, Type, Value};
+
+use crate::ast::{BType, Span};
+use crate::ir::{Diagnostics, ErrorKind, Result, WarningKind};
+
 use super::func::FunctionInfo;
+use super::values::Num;
 
-pub struct Scopes {
+pub struct Scopes<'ast> {
+  vals: Vec<HashMap<&'ast str, Symbol>>,
+  /// Unused local variables in each scope, and their definitions.
+  unused: Vec<HashMap<&'ast str, Span>>,
+  funcs: HashMap<&'ast str, (Function, FuncSig)>,
   pub cur_func: Option<FunctionInfo>,
+  loop_info: Vec<LoopInfo>,
+  pub diags: Diagnostics,
 }
 
 /// Returns a reference to the current function information.
@@ -20,16 +35,162 @@ macro_rules! cur_func_mut {
 }
 pub(crate) use cur_func_mut;
 
-impl Scopes {
+impl<'ast> Scopes<'ast> {
     /// Creates a new `Scopes`.
     pub fn new() -> Self {
         Self {
+            vals: vec![HashMap::new()],
+            unused: vec![HashMap::new()],
+            funcs: HashMap::new(),
             cur_func: None,
+            loop_info: Vec::new(),
+            diags: Diagnostics::default(),
         }
     }
 
     /// Returns `true` if is currently in global scope.
-    pub fn _is_global(&self) -> bool {
+    pub fn is_global(&self) -> bool {
         self.cur_func.is_none()
     }
+
+    /// Returns the type of the given value.
+    pub fn ty(&self, program: &Program, value: Value) -> Type {
+        if value.is_global() {
+            program.borrow_value(value).ty().clone()
+        } else {
+            let func = program.func(cur_func!(self).func());
+            func.dfg().value(value).ty().clone()
+        }
+    }
+
+    /// Inserts a new symbol into the current scope.
+    ///
+    /// Global variables and functions share the same namespace.
+    pub fn new_value(&mut self, id: &'ast str, value: Symbol) -> Result<()> {
+        let is_global = self.vals.len() == 1;
+        let cur = self.vals.last_mut().unwrap();
+        if cur.contains_key(id) || is_global && self.funcs.contains_key(id) {
+            Err(ErrorKind::DuplicatedDef(id.into()).into())
+        } else {
+            cur.insert(id, value);
+            Ok(())
+        }
+    }
+
+    /// Returns `true` if the given identifier is defined in an outer scope.
+    pub fn is_shadowing(&self, id: &str) -> bool {
+        self.vals.iter().rev().skip(1).any(|vals| vals.contains_key(id))
+    }
+
+    /// Tracks the given variable in the current scope,
+    /// it will be reported as unused if it is never referenced.
+    pub fn track_unused(&mut self, id: &'ast str, span: Span) {
+        self.unused.last_mut().unwrap().insert(id, span);
+    }
+
+    /// Marks the symbol by the given identifier as used.
+    pub fn mark_used(&mut self, id: &str) {
+        if let Some(i) = self.vals.iter().rposition(|vals| vals.contains_key(id)) {
+            self.unused[i].remove(id);
+        }
+    }
+
+    /// Returns the symbol by the given identifier,
+    /// searching from the innermost scope outward.
+    pub fn value(&self, id: &str) -> Result<Symbol> {
+        self.vals
+            .iter()
+            .rev()
+            .find_map(|vals| vals.get(id).cloned())
+            .ok_or_else(|| ErrorKind::SymbolNotFound(id.into()).into())
+    }
+
+    /// Inserts a new function with the given signature.
+    pub fn new_func(&mut self, id: &'ast str, func: Function, sig: FuncSig) -> Result<()> {
+        if self.funcs.contains_key(id) || self.vals[0].contains_key(id) {
+            Err(ErrorKind::DuplicatedDef(id.into()).into())
+        } else {
+            self.funcs.insert(id, (func, sig));
+            Ok(())
+        }
+    }
+
+    /// Returns the function by the given identifier and its signature.
+    pub fn func(&self, id: &str) -> Result<(Function, FuncSig)> {
+        self.funcs
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ErrorKind::SymbolNotFound(id.into()).into())
+    }
+
+    /// Returns all functions and their signatures.
+    pub fn funcs(&self) -> impl Iterator<Item = &(Function, FuncSig)> {
+        self.funcs.values()
+    }
+
+    /// Enters a new scope.
+    pub fn enter(&mut self) {
+        self.vals.push(HashMap::new());
+        self.unused.push(HashMap::new());
+    }
+
+    /// Exits from the current scope, reports unused variables in it.
+    pub fn exit(&mut self) {
+        self.vals.pop();
+        for (id, span) in self.unused.pop().unwrap() {
+            self.diags.warn(WarningKind::UnusedVar(id.into()).at(span));
+        }
+    }
+
+    /// Enters a new loop.
+    pub fn enter_loop(&mut self, entry: BasicBlock, exit: BasicBlock) {
+        self.loop_info.push(LoopInfo { entry, exit });
+    }
+
+    /// Exits from the current loop.
+    pub fn exit_loop(&mut self) {
+        self.loop_info.pop();
+    }
+
+    /// Returns the information of the innermost loop,
+    /// or `None` if not in a loop.
+    pub fn loop_info(&self) -> Option<LoopInfo> {
+        self.loop_info.last().copied()
+    }
+}
+
+/// A symbol in scopes.
+#[derive(Clone)]
+pub enum Symbol {
+    /// A variable, holds the allocation of it and its base type.
+    Var(Value, BType),
+    /// A constant, holds the evaluated value of it.
+    Const(Num),
+    /// A constant array, holds the allocation of it and its evaluated elements.
+    ConstArray(Value, Rc<ConstElems>),
+}
+
+/// Evaluated elements of a constant array.
+pub struct ConstElems {
+    pub ty: BType,
+    pub dims: Vec<usize>,
+    /// Elements in row-major order.
+    pub elems: Vec<Num>,
+}
+
+/// Base types of parameters and the return value of a function.
+#[derive(Clone)]
+pub struct FuncSig {
+    pub params: Vec<BType>,
+    /// `None` if the function returns `void`.
+    pub ret: Option<BType>,
+}
+
+/// Information of a loop.
+#[derive(Clone, Copy)]
+pub struct LoopInfo {
+    /// Basic block that `continue` jumps to.
+    pub entry: BasicBlock,
+    /// Basic block that `break` jumps to.
+    pub exit: BasicBlock,
 }
@@ -0,0 +1,159 @@
+use koopa::ir::Value;
+
+use crate::ast::BType;
+use crate::ir::{ErrorKind, Result};
+
+/// Value of an expression.
+///
+/// Koopa IR has no floating-point type, `float` values are represented
+/// by their bit patterns in `i32`, and their types are tracked here.
+pub enum ExpValue {
+    /// Nothing, e.g. the result of calling a `void` function.
+    Void,
+    /// An integer.
+    Int(Value),
+    /// A `float`.
+    Float(Value),
+    /// A pointer to a number of the given type, e.g. an element of an array.
+    Ptr(Value, BType),
+    /// A pointer to the first element of an array of the given type,
+    /// e.g. an array parameter or a partially indexed array.
+    ArrPtr(Value, BType),
+}
+
+impl ExpValue {
+    /// Creates a number of the given type.
+    pub fn num(value: Value, ty: BType) -> Self {
+        match ty {
+            BType::Int => Self::Int(value),
+            BType::Float => Self::Float(value),
+        }
+    }
+
+    /// Converts the value into a number and its type,
+    /// returns error if the value is `void` or a pointer.
+    pub fn into_num(self) -> Result<(Value, BType)> {
+        match self {
+            Self::Int(value) => Ok((value, BType::Int)),
+            Self::Float(value) => Ok((value, BType::Float)),
+            Self::Void => Err(ErrorKind::UseVoidValue.into()),
+            Self::Ptr(..) => Err(ErrorKind::PtrAsInt.into()),
+            Self::ArrPtr(..) => Err(ErrorKind::NonIntCalc.into()),
+        }
+    }
+
+    /// Converts the value into a pointer to a number and the type of
+    /// the number, returns error if the value is not an lvalue.
+    pub fn into_ptr(self) -> Result<(Value, BType)> {
+        match self {
+            Self::Ptr(ptr, ty) => Ok((ptr, ty)),
+            _ => Err(ErrorKind::NotAnLValue.into()),
+        }
+    }
+}
+
+/// A number evaluated at compile time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Num {
+    Int(i32),
+    Float(f32),
+}
+
+impl Num {
+    /// Returns the type of the number.
+    pub fn ty(self) -> BType {
+        match self {
+            Self::Int(_) => BType::Int,
+            Self::Float(_) => BType::Float,
+        }
+    }
+
+    /// Returns the number converted to `float`.
+    pub fn float(self) -> f32 {
+        match self {
+            Self::Int(num) => num as f32,
+            Self::Float(num) => num,
+        }
+    }
+
+    /// Converts the number to the given type,
+    /// `float` is truncated toward zero when converted to `int`.
+    pub fn to(self, ty: BType) -> Self {
+        match (self, ty) {
+            (Self::Float(num), BType::Int) => Self::Int(num as i32),
+            (Self::Int(num), BType::Float) => Self::Float(num as f32),
+            _ => self,
+        }
+    }
+
+    /// Returns `true` if the number is zero (including `-0.0`).
+    pub fn is_zero(self) -> bool {
+        self.float() == 0.0
+    }
+
+    /// Returns the representation of the number in Koopa IR.
+    pub fn bits(self) -> i32 {
+        match self {
+            Self::Int(num) => num,
+            Self::Float(num) => num.to_bits() as i32,
+        }
+    }
+}
+
+/// An initializer of a variable or constant.
+pub enum Initializer {
+    /// A constant number.
+    Const(Num),
+    /// A number of the given type computed at runtime.
+    Value(Value, BType),
+    /// An initializer list.
+    List(Vec<Initializer>),
+}
+
+impl Initializer {
+    /// Flattens the initializer of a variable with the given dimensions
+    /// (empty for scalars), returns all of its elements in row-major order.
+    ///
+    /// Follows the SysY rules: a nested list initializes the largest
+    /// sub-array aligned to the current position, and elements that are
+    /// not explicitly initialized are filled with zero.
+    pub fn flatten(self, dims: &[usize]) -> Result<Vec<Self>> {
+        match self {
+            Self::List(list) if !dims.is_empty() => {
+                let len = dims.iter().product();
+                let mut elems = Vec::with_capacity(len);
+                Self::fill(list, dims, &mut elems)?;
+                Ok(elems)
+            }
+            Self::List(_) => Err(ErrorKind::InvalidInit.into()),
+            _ if !dims.is_empty() => Err(ErrorKind::InvalidInit.into()),
+            init => Ok(vec![init]),
+        }
+    }
+
+    /// Fills the given initializer list of an array into `elems`.
+    fn fill(list: Vec<Self>, dims: &[usize], elems: &mut Vec<Self>) -> Result<()> {
+        let len: usize = dims.iter().product();
+        let start = elems.len();
+        for init in list {
+            let pos = elems.len() - start;
+            if pos >= len {
+                return Err(ErrorKind::InvalidInit.into());
+            }
+            match init {
+                Self::List(list) => {
+                    // find the largest aligned sub-array
+                    let sub_dims = (1..dims.len())
+                        .map(|i| &dims[i..])
+                        .find(|d| pos.is_multiple_of(d.iter().product()))
+                        .ok_or(ErrorKind::InvalidInit)?;
+                    Self::fill(list, sub_dims, elems)?;
+                }
+                init => elems.push(init),
+            }
+        }
+        // fill the remaining elements with zero
+        elems.resize_with(start + len, || Self::Const(Num::Int(0)));
+        Ok(())
+    }
+}
@@ -1,20 +1,30 @@
 mod ast;
 mod code;
 mod ir;
+mod source;
 
+use std::collections::HashSet;
 use std::env::args;
 use std::fmt;
 use std::fs::read_to_string;
 use std::io;
 use std::process::exit;
 
+use ast::{CompUnit, Span, UserError};
+use code::RegAlloc;
+use ir::{FloatSigs, WarningKind};
 use koopa::back::KoopaGenerator;
-use lalrpop_util::lalrpop_mod;
+use koopa::front::Driver;
+use koopa::ir::Program;
+use lalrpop_util::{lalrpop_mod, ParseError};
+use source::Source;
 
-lalrpop_mod!(sysy);
+lalrpop_mod!(#[allow(clippy::all)] sysy);
 
 // cargo run -- -koopa input/hello.c -o output/hello.koopa
 // cargo run -- -riscv input/hello.c -o output/hello.asm
+// cargo run -- -perf input/hello.c -o output/hello.asm
+// cargo run -- -riscv input/hello.koopa -o output/hello.asm
 fn main() {
     if let Err(err) = try_main() {
         eprintln!("{}", err);
@@ -28,17 +38,21 @@ fn try_main() -> Result<(), Error> {
         mode,
         input,
         output,
+        reg_alloc,
+        warnings,
     } = CommandLineArgs::parse()?;
 
-    // parse input file
-    let input = read_to_string(input).map_err(Error::File)?;
-    let program_ast = sysy::CompUnitParser::new()
-        .parse(&input)
-        .map_err(|_| Error::Parse)?;
-    println!("AST:\n{:#?}", program_ast);
-
-    // generate IR
-    let program_ir = ir::generate_program(&program_ast).map_err(Error::Generate)?;
+    // Koopa IR input skips the SysY frontend, and has no `float`s
+    let (program_ir, float_sigs) = if input.ends_with(".koopa") {
+        let text = read_to_string(input).map_err(Error::File)?;
+        let program = Driver::from(text)
+            .generate_program()
+            .map_err(|_| Error::InvalidKoopa)?;
+        (program, FloatSigs::new())
+    } else {
+        let text = read_to_string(&input).map_err(Error::File)?;
+        generate_ir(&Source::new(input, text), &warnings)?
+    };
     if matches!(mode, Mode::Koopa) {
         return KoopaGenerator::from_path(output)
           .map_err(Error::File)?
@@ -47,14 +61,151 @@ fn try_main() -> Result<(), Error> {
     }
 
     // generate RISC-V assembly
-    code::generate_asm(&program_ir, &output).map_err(Error::Io)
+    code::generate_asm(&program_ir, &float_sigs, &output, reg_alloc).map_err(Error::Io)
+}
+
+/// Diagnostic level, message and location.
+type Diagnostic = (&'static str, String, Option<Span>);
+
+/// Compiles the given SysY source file to Koopa IR and signatures of
+/// functions with `float`s, reports warnings enabled in `warnings`.
+fn generate_ir(
+    source: &Source,
+    warnings: &HashSet<&str>,
+) -> Result<(Program, FloatSigs), Error> {
+    // parse input file, semantic analysis still runs on the recovered AST
+    let (program_ast, parse_errors) = parse(source);
+    let report = |diags: Vec<Diagnostic>| -> Vec<_> {
+        diags
+            .into_iter()
+            .map(|(level, msg, span)| source.report(level, &msg, span))
+            .collect()
+    };
+    let program_ast = match program_ast {
+        Some(ast) => ast,
+        None => return Err(Error::Parse(report(parse_errors))),
+    };
+    println!("AST:\n{:#?}", program_ast);
+
+    // generate IR, warnings on a partially parsed program may be spurious
+    let (program_ir, float_sigs, mut diags) = ir::generate_program(&program_ast);
+    diags.retain_warnings(|name| parse_errors.is_empty() && warnings.contains(name));
+    let has_errors = !parse_errors.is_empty() || diags.has_errors();
+    let mut all = parse_errors;
+    all.extend(diags.sorted());
+    all.sort_by_key(|(_, _, span)| span.map(|s| s.start));
+    let msgs = report(all);
+    if has_errors {
+        return Err(Error::Generate(msgs));
+    }
+    for msg in msgs {
+        eprintln!("{msg}\n");
+    }
+    Ok((program_ir, float_sigs))
+}
+
+/// Parses the given source file, returns the AST and the parse errors.
+///
+/// Errors are recovered by the parser if possible, the AST is `None`
+/// only if the parser failed to recover.
+fn parse(source: &Source) -> (Option<CompUnit>, Vec<Diagnostic>) {
+    let mut errors = Vec::new();
+    let result = sysy::CompUnitParser::new().parse(&mut errors, source.text());
+    let mut diags: Vec<_> = errors
+        .into_iter()
+        .map(|e| parse_error(source, e.error))
+        .collect();
+    let ast = match result {
+        Ok(ast) => Some(ast),
+        Err(err) => {
+            diags.push(parse_error(source, err));
+            None
+        }
+    };
+    (ast, diags)
+}
+
+/// Converts the given parse error to a diagnostic with its location.
+fn parse_error<T: fmt::Display>(
+    source: &Source,
+    err: ParseError<usize, T, UserError>,
+) -> Diagnostic {
+    let (msg, span) = match err {
+        ParseError::InvalidToken { location } => {
+            // the invalid token may be a multi-byte character
+            let len = source.text()[location..].chars().next().map_or(1, char::len_utf8);
+            ("invalid token".into(), Span::new(location, location + len))
+        }
+        ParseError::UnrecognizedEOF { location, expected } => (
+            format!("unexpected end of file{}", expected_tokens(&expected)),
+            Span::new(location, location),
+        ),
+        ParseError::UnrecognizedToken {
+            token: (l, token, r),
+            expected,
+        } => (
+            format!("unexpected token `{token}`{}", expected_tokens(&expected)),
+            Span::new(l, r),
+        ),
+        ParseError::ExtraToken { token: (l, token, r) } => {
+            (format!("extra token `{token}`"), Span::new(l, r))
+        }
+        ParseError::User {
+            error: (span, msg),
+        } => (msg.into(), span),
+    };
+    ("error", msg, Some(span))
+}
+
+/// Regular expression tokens in the grammar, as reported by the parser,
+/// and their names.
+const REGEX_TOKENS: [(&str, &str); 6] = [
+    (r##"r#"[_a-zA-Z][_a-zA-Z0-9]*"#"##, "identifier"),
+    (r##"r#"[1-9][0-9]*"#"##, "integer literal"),
+    (r##"r#"0[0-7]*"#"##, "integer literal"),
+    (r##"r#"0[xX][0-9a-fA-F]+"#"##, "integer literal"),
+    (
+        r##"r#"([0-9]*\\.[0-9]+|[0-9]+\\.)([eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+"#"##,
+        "floating-point literal",
+    ),
+    (
+        r##"r#"0[xX]([0-9a-fA-F]*\\.[0-9a-fA-F]+|[0-9a-fA-F]+\\.?)[pP][+-]?[0-9]+"#"##,
+        "floating-point literal",
+    ),
+];
+
+/// Formats the expected token set reported by the parser.
+fn expected_tokens(expected: &[String]) -> String {
+    let mut names = Vec::new();
+    for token in expected {
+        let name = match REGEX_TOKENS.iter().find(|(regex, _)| regex == token) {
+            Some((_, name)) => name.to_string(),
+            None => match token.strip_prefix('"') {
+                // literal tokens, e.g. `"\";\""`
+                Some(token) => format!("`{}`", token.trim_end_matches('"')),
+                None => token.clone(),
+            },
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.as_slice() {
+        [] => String::new(),
+        [name] => format!(", expected {name}"),
+        _ => format!(", expected one of {}", names.join(", ")),
+    }
 }
 
 /// Error returned by `main` procedure.
 enum Error {
     InvalidArgs,
-    Parse,
-    Generate(ir::Error),
+    /// Rendered messages of the unrecoverable parse errors.
+    Parse(Vec<String>),
+    /// Rendered parse error, IR generation error and warning messages.
+    Generate(Vec<String>),
+    /// Errors in Koopa IR input have been reported by its parser.
+    InvalidKoopa,
     File(io::Error),
     Io(io::Error),
 }
@@ -64,15 +215,22 @@ impl fmt::Display for Error {
         match self {
             Self::InvalidArgs => write!(
                 f,
-                r#"Usage: kira MODE INPUT -o OUTPUT
+                r#"Usage: kira MODE INPUT -o OUTPUT [OPT] [WARNINGS]
 
     Options:
-        MODE:   can be `-koopa`, `-riscv` or `-perf`
-        INPUT:  the input SysY source file
-        OUTPUT: the output file"#
+        MODE:     can be `-koopa`, `-riscv` or `-perf`
+        INPUT:    the input SysY source file, or Koopa IR file if it
+                  ends with `.koopa`
+        OUTPUT:   the output file
+        OPT:      `-O0` (no register allocation), `-O1` (linear scan,
+                  default of `-riscv`) or `-O2` (graph coloring,
+                  default of `-perf`)
+        WARNINGS: `-Wall`, `-W<name>` or `-Wno-<name>`, where <name> can be
+                  `unused-variable`, `unused-result`, `unreachable-code`,
+                  `div-by-zero`, `shadow` or `return-type`"#
             ),
-            Self::Parse => write!(f, "error occurred while parsing"),
-            Self::Generate(err) => write!(f, "{}", err),
+            Self::Parse(msgs) | Self::Generate(msgs) => write!(f, "{}", msgs.join("\n\n")),
+            Self::InvalidKoopa => write!(f, "invalid input Koopa IR file"),
             Self::File(err) => write!(f, "invalid input SysY file: {}", err),
             Self::Io(err) => write!(f, "I/O error: {}", err),
         }
@@ -84,6 +242,9 @@ struct CommandLineArgs {
     mode: Mode,
     input: String,
     output: String,
+    reg_alloc: RegAlloc,
+    /// Names of enabled warnings.
+    warnings: HashSet<&'static str>,
 }
 
 impl CommandLineArgs {
@@ -91,28 +252,142 @@ impl CommandLineArgs {
     fn parse() -> Result<Self, Error> {
         let mut args = args();
         args.next();
-        match (args.next(), args.next(), args.next(), args.next()) {
+        let mut cmd_args = match (args.next(), args.next(), args.next(), args.next()) {
             (Some(m), Some(input), Some(o), Some(output)) if o == "-o" => {
             let mode = match m.as_str() {
                 "-koopa" => Mode::Koopa,
                 "-riscv" => Mode::Riscv,
+                "-perf" => Mode::Perf,
                 _ => return Err(Error::InvalidArgs),
             };
-            Ok(Self {
+            let reg_alloc = match mode {
+                Mode::Perf => RegAlloc::GraphColoring,
+                _ => RegAlloc::LinearScan,
+            };
+            Self {
                 mode,
                 input,
                 output,
-            })
+                reg_alloc,
+                warnings: WarningKind::ALL
+                    .iter()
+                    .filter_map(|&(name, default)| default.then_some(name))
+                    .collect(),
+            }
+            }
+            _ => return Err(Error::InvalidArgs),
+        };
+
+        // parse optimization and warning options
+        for arg in args {
+            if let Some(level) = arg.strip_prefix("-O") {
+                cmd_args.reg_alloc = match level {
+                    "0" => RegAlloc::None,
+                    "1" => RegAlloc::LinearScan,
+                    "2" => RegAlloc::GraphColoring,
+                    _ => return Err(Error::InvalidArgs),
+                };
+            } else if arg == "-Wall" {
+                cmd_args.warnings.extend(WarningKind::ALL.iter().map(|(name, _)| *name));
+            } else if let Some(name) = arg.strip_prefix("-Wno-") {
+                cmd_args.warnings.remove(warning_name(name)?);
+            } else if let Some(name) = arg.strip_prefix("-W") {
+                cmd_args.warnings.insert(warning_name(name)?);
+            } else {
+                return Err(Error::InvalidArgs);
             }
-            _ => Err(Error::InvalidArgs),
         }
+        Ok(cmd_args)
     }
 }
 
+/// Returns the name of the warning, or `Error` if there is no such warning.
+fn warning_name(name: &str) -> Result<&'static str, Error> {
+    WarningKind::ALL
+        .iter()
+        .map(|(n, _)| *n)
+        .find(|n| *n == name)
+        .ok_or(Error::InvalidArgs)
+}
+
 /// Compile mode.
 enum Mode {
     /// Compile SysY to Koopa IR.
     Koopa,
     /// Compile SysY to RISC-V assembly.
     Riscv,
+    /// Compile SysY to RISC-V assembly, optimized for performance.
+    Perf,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(text: &str) -> Vec<String> {
+        match generate_ir(&Source::new("test.c".into(), text.into()), &HashSet::new()) {
+            Err(Error::Parse(msgs) | Error::Generate(msgs)) => msgs,
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn invalid_non_ascii_token() {
+        let errors = errors("int main() { int é = 1; return 0; }");
+        assert_eq!(
+            errors[0],
+            "error: invalid token\n --> test.c:1:18\n  |\n\
+             1 | int main() { int é = 1; return 0; }\n  |                  ^"
+        );
+    }
+
+    #[test]
+    fn semantic_errors_after_parse_errors() {
+        let errors = errors("int f() { return 1 }\nint main() { return x; }");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("error: unexpected token `}`"));
+        assert!(errors[1].contains("test.c:2:21"));
+    }
+
+    #[test]
+    fn expected_literals() {
+        let errors = errors("int main() { int a = ; }");
+        assert!(errors[0].starts_with("error: unexpected token `;`, expected one of "));
+        for name in ["identifier", "integer literal", "floating-point literal"] {
+            assert!(errors[0].contains(name));
+        }
+        assert!(!errors[0].contains("r#"));
+    }
+
+    #[test]
+    fn int_literal_too_large() {
+        let text = "int a = 2147483648, b = 0xFFFFFFFF, c = 037777777777;\n\
+                    int d = 2147483649, e = 4294967295, f = 0x100000000;\n\
+                    int main() { return 0; }";
+        let errors = errors(text);
+        let cols: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                assert!(e.starts_with("error: integer literal is too large"));
+                e.lines().nth(1).unwrap()
+            })
+            .collect();
+        assert_eq!(cols, [" --> test.c:2:9", " --> test.c:2:25", " --> test.c:2:41"]);
+    }
+
+    #[test]
+    fn float_literal_too_large() {
+        let text = "float a = 3.4e38, b = 0x1.fffffep127, c = 0x1p-99999999999;\n\
+                    float d = 3.5e38, e = 0x1p128, f = 0x1p99999999999;\n\
+                    int main() { return 0; }";
+        let errors = errors(text);
+        let cols: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                assert!(e.starts_with("error: floating-point literal is too large"));
+                e.lines().nth(1).unwrap()
+            })
+            .collect();
+        assert_eq!(cols, [" --> test.c:2:11", " --> test.c:2:23", " --> test.c:2:36"]);
+    }
 }
@@ -0,0 +1,137 @@
+use crate::ast::Span;
+
+/// A SysY source file.
+pub struct Source {
+    name: String,
+    text: String,
+    /// Byte offsets of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    /// Creates a new source file by the given name and content.
+    pub fn new(name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    /// Returns the content of the source file.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the line and column number (both 1-based)
+    /// of the given byte offset.
+    pub fn line_col(&self, pos: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= pos) - 1;
+        let col = self.text[self.line_starts[line]..pos].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// Returns the content of the given line (1-based),
+    /// without the line terminator.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// Clamps the given span to the source text and char boundaries.
+    fn clamp(&self, span: Span) -> Span {
+        let mut start = span.start.min(self.text.len());
+        while !self.text.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = span.end.clamp(start, self.text.len());
+        while !self.text.is_char_boundary(end) {
+            end += 1;
+        }
+        Span::new(start, end)
+    }
+
+    /// Renders a diagnostic message of the given level (e.g. `error`),
+    /// with the location and a snippet of the source pointed by the span.
+    ///
+    /// ```text
+    /// error: unexpected token `}`, expected `;`
+    ///  --> hello.c:3:1
+    ///   |
+    /// 3 | }
+    ///   | ^
+    /// ```
+    pub fn report(&self, level: &str, msg: &str, span: Option<Span>) -> String {
+        let span = match span {
+            Some(span) => self.clamp(span),
+            None => return format!("{level}: {msg}\n  --> {}", self.name),
+        };
+        let (line, col) = self.line_col(span.start);
+        let text = self.line(line);
+
+        // keep tabs in the padding so that the caret lines up
+        let pad: String = text
+            .chars()
+            .take(col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // underline the span, but only within the first line
+        let rest = text.chars().count().saturating_sub(col - 1);
+        let len = self.text[span.start..span.end]
+            .chars()
+            .count()
+            .min(rest)
+            .max(1);
+
+        let name = &self.name;
+        let blank = " ".repeat(line.to_string().len());
+        let caret = "^".repeat(len);
+        [
+            format!("{level}: {msg}"),
+            format!("{blank}--> {name}:{line}:{col}"),
+            format!("{blank} |"),
+            format!("{line} | {text}"),
+            format!("{blank} | {pad}{caret}"),
+        ]
+        .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col_counts_chars() {
+        let source = Source::new("a.c".into(), "int é;\n  x".into());
+        assert_eq!(source.line_col(0), (1, 1));
+        assert_eq!(source.line_col(7), (1, 7));
+        assert_eq!(source.line_col(10), (2, 3));
+    }
+
+    #[test]
+    fn report_non_ascii_span() {
+        let source = Source::new("a.c".into(), "int é = 1;\n".into());
+        // a span ending inside `é` is extended to the char boundary
+        let msg = source.report("error", "invalid token", Some(Span::new(4, 5)));
+        assert_eq!(
+            msg,
+            "error: invalid token\n --> a.c:1:5\n  |\n1 | int é = 1;\n  |     ^"
+        );
+    }
+
+    #[test]
+    fn report_span_out_of_range() {
+        let source = Source::new("a.c".into(), "int".into());
+        let msg = source.report("error", "unexpected end of file", Some(Span::new(3, 9)));
+        assert!(msg.ends_with("1 | int\n  |    ^"));
+    }
+}